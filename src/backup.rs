@@ -0,0 +1,231 @@
+//! Backing up the fingerprint library to the host, and restoring it to the same or another module.
+//!
+//! An archive records the module it was taken from: library size, security level and firmware version.
+//! It holds the templates the way UpChar sends them, each with the ModelID it came from, when it was backed up,
+//! a label, and a CRC-32 of the template. All numbers are big-endian, as in the packages; text is UTF-8 after its length in bytes.
+//!
+//! ```text
+//! "R503LIB\0"  version: u16  library size: u16  security level: u16  firmware version: u8 + text  templates: u16
+//! per template: ModelID: u16  backed up at: u64 (seconds since the Unix epoch)  label: u8 + text  template: 1536 bytes  CRC-32: u32
+//! ```
+
+use std::boxed::Box;
+use std::io;
+use std::string::String;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::vec::Vec;
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read, Write};
+
+use crate::{BufferID, Driver, Error, ModelId, TemplateData};
+
+const MAGIC: [u8; 8] = *b"R503LIB\0";
+/// Format version written by `Archive::write_to`.
+pub const VERSION: u16 = 1;
+
+/// One template of the library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Where the template was stored, and where `restore_library` stores it again.
+    pub model_id: ModelId,
+    /// When the template was uploaded, in seconds since the Unix epoch.
+    pub backed_up: u64,
+    /// Whose finger it is, or anything else worth keeping with the template; up to 255 bytes.
+    /// `backup_library` leaves it empty.
+    pub label: String,
+    pub template: Box<TemplateData>,
+}
+
+/// The templates of a fingerprint library.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Archive {
+    /// Size of the library the templates come from.
+    pub library_size: u16,
+    /// Security level (1, 2, 3, 4, 5) of the module the templates come from.
+    pub security_level: u16,
+    /// Firmware version of the module the templates come from, as GetFwVer reports it.
+    pub firmware: String,
+    /// In order of ModelID.
+    pub entries: Vec<Entry>,
+}
+
+/// Why an archive couldn't be read.
+#[derive(Debug)]
+pub enum ArchiveError {
+    Io(io::Error),
+    /// Not an archive.
+    Magic,
+    /// Written by another version of the format.
+    Version(u16),
+    /// A ModelID beyond the library.
+    ModelId(u16),
+    /// The template at this ModelID is corrupted.
+    Checksum(ModelId),
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl Archive {
+    pub fn write_to(&self, mut out: impl io::Write) -> io::Result<()> {
+        out.write_all(&MAGIC)?;
+        out.write_all(&VERSION.to_be_bytes())?;
+        out.write_all(&self.library_size.to_be_bytes())?;
+        out.write_all(&self.security_level.to_be_bytes())?;
+        write_text(&mut out, &self.firmware)?;
+        let count = u16::try_from(self.entries.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "too many templates"))?;
+        out.write_all(&count.to_be_bytes())?;
+
+        for entry in &self.entries {
+            out.write_all(&entry.model_id.get().to_be_bytes())?;
+            out.write_all(&entry.backed_up.to_be_bytes())?;
+            write_text(&mut out, &entry.label)?;
+            out.write_all(&entry.template[..])?;
+            out.write_all(&crc32(&entry.template[..]).to_be_bytes())?;
+        }
+        out.flush()
+    }
+
+    pub fn read_from(mut input: impl io::Read) -> Result<Self, ArchiveError> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(ArchiveError::Magic);
+        }
+        let version = read_u16(&mut input)?;
+        if version != VERSION {
+            return Err(ArchiveError::Version(version));
+        }
+        let library_size = read_u16(&mut input)?;
+        let security_level = read_u16(&mut input)?;
+        let firmware = read_text(&mut input)?;
+        let count = read_u16(&mut input)?;
+
+        let mut entries = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let id = read_u16(&mut input)?;
+            let model_id = ModelId::new(id, library_size).ok_or(ArchiveError::ModelId(id))?;
+            let mut backed_up = [0; 8];
+            input.read_exact(&mut backed_up)?;
+            let label = read_text(&mut input)?;
+            let mut template = Box::new([0; 1536]);
+            input.read_exact(&mut template[..])?;
+            let mut checksum = [0; 4];
+            input.read_exact(&mut checksum)?;
+            if u32::from_be_bytes(checksum) != crc32(&template[..]) {
+                return Err(ArchiveError::Checksum(model_id));
+            }
+
+            entries.push(Entry {
+                model_id,
+                backed_up: u64::from_be_bytes(backed_up),
+                label,
+                template,
+            });
+        }
+
+        Ok(Self {
+            library_size,
+            security_level,
+            firmware,
+            entries,
+        })
+    }
+}
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Uploads every template in the library, as the index table lists them, through CharBuffer1.
+    /// Records the module's security level and firmware version with them.
+    pub async fn backup_library(&mut self) -> Result<Archive, Error<UART::Error>> {
+        let parameters = self.read_sys_para().await?;
+        let firmware = self.get_fw_ver().await?;
+        let index = self.read_library_index().await?;
+        let mut entries = Vec::with_capacity(index.count() as usize);
+        for model_id in index.iter_occupied() {
+            self.load_char(BufferID::CharBuffer1, model_id).await?;
+            let mut template = Box::new([0; 1536]);
+            if self.up_char(BufferID::CharBuffer1, &mut template).await? != template.len() {
+                return Err(Error::IncompleteData);
+            }
+
+            entries.push(Entry {
+                model_id,
+                backed_up: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |since| since.as_secs()),
+                label: String::new(),
+                template,
+            });
+        }
+
+        Ok(Archive {
+            library_size: index.library_size(),
+            security_level: parameters.security_level,
+            firmware: firmware.as_str().into(),
+            entries,
+        })
+    }
+
+    /// Downloads every template of `archive` through CharBuffer1 and stores it at its ModelID,
+    /// replacing whatever is there. Reads the module's library size first, and fails with `Error::InvalidArgument`
+    /// before storing anything if the library is too small for the archive.
+    pub async fn restore_library(&mut self, archive: &Archive) -> Result<(), Error<UART::Error>> {
+        let library_size = self.read_sys_para().await?.finger_libary_size;
+        if archive
+            .entries
+            .iter()
+            .any(|entry| entry.model_id.get() >= library_size)
+        {
+            return Err(Error::InvalidArgument);
+        }
+
+        for entry in &archive.entries {
+            self.down_char(BufferID::CharBuffer1, &entry.template)
+                .await?;
+            self.store(BufferID::CharBuffer1, entry.model_id).await?;
+        }
+        Ok(())
+    }
+}
+
+fn read_u16(input: &mut impl io::Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    input.read_exact(&mut bytes)?;
+    Ok(u16::from_be_bytes(bytes))
+}
+
+fn write_text(out: &mut impl io::Write, text: &str) -> io::Result<()> {
+    let length = u8::try_from(text.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "text too long"))?;
+    out.write_all(&[length])?;
+    out.write_all(text.as_bytes())
+}
+
+fn read_text(input: &mut impl io::Read) -> io::Result<String> {
+    let mut length = [0];
+    input.read_exact(&mut length)?;
+    let mut bytes = std::vec![0; length[0] as usize];
+    input.read_exact(&mut bytes)?;
+    String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+/// CRC-32 as in zip and PNG.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
@@ -0,0 +1,123 @@
+//! Several modules on one UART.
+//!
+//! Every module only answers packages sent to its own address (see `Driver::set_adder`),
+//! so modules with different addresses can share the TX and RX lines.
+//! A `SensorBus` owns the UART and hands out a `Driver` per address; `Driver::exclusive` keeps their exchanges apart.
+//!
+//! **Only talk to a sensor on a shared bus inside `Driver::exclusive`.** Outside of it, every single read and write
+//! takes the bus on its own, so another sensor's exchange can come in between and either driver can consume the other's bytes.
+
+use embassy_sync::blocking_mutex::raw::RawMutex;
+use embassy_sync::mutex::{Mutex, MutexGuard};
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{ErrorType, Read, Write};
+
+use crate::{Address, Driver, DriverBuilder, Error};
+
+/// A UART shared by modules with different addresses.
+pub struct SensorBus<M: RawMutex, UART> {
+    line: Mutex<M, Line<UART>>,
+}
+
+/// The shared UART, and whether the last sensor gave it up with an answer still on its way.
+struct Line<UART> {
+    uart: UART,
+    unsettled: bool,
+}
+
+impl<M: RawMutex, UART: Read + Write> SensorBus<M, UART> {
+    pub fn new(uart: UART) -> Self {
+        Self {
+            line: Mutex::new(Line {
+                uart,
+                unsettled: false,
+            }),
+        }
+    }
+
+    /// Configures a driver for the module at `address`. Run all of its operations through `Driver::exclusive`.
+    pub fn sensor(&self, address: Address) -> DriverBuilder<BusUart<'_, M, UART>> {
+        Driver::builder(BusUart {
+            bus: self,
+            guard: None,
+        })
+        .address(address)
+    }
+
+    pub fn into_inner(self) -> UART {
+        self.line.into_inner().uart
+    }
+}
+
+/// One sensor's end of a `SensorBus`.
+/// Inside `Driver::exclusive` it holds the bus. Outside, every read and write takes the bus on its own,
+/// which is unsafe on a bus shared with other sensors: see the module documentation.
+pub struct BusUart<'a, M: RawMutex, UART> {
+    bus: &'a SensorBus<M, UART>,
+    guard: Option<MutexGuard<'a, M, Line<UART>>>,
+}
+
+impl<M: RawMutex, UART: ErrorType> ErrorType for BusUart<'_, M, UART> {
+    type Error = UART::Error;
+}
+
+impl<M: RawMutex, UART: Read> Read for BusUart<'_, M, UART> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match &mut self.guard {
+            Some(line) => line.uart.read(buf).await,
+            None => self.bus.line.lock().await.uart.read(buf).await,
+        }
+    }
+}
+
+impl<M: RawMutex, UART: Write> Write for BusUart<'_, M, UART> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        match &mut self.guard {
+            Some(line) => line.uart.write(buf).await,
+            None => self.bus.line.lock().await.uart.write(buf).await,
+        }
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        match &mut self.guard {
+            Some(line) => line.uart.flush().await,
+            None => self.bus.line.lock().await.uart.flush().await,
+        }
+    }
+}
+
+impl<'a, M, UART, DELAY> Driver<BusUart<'a, M, UART>, DELAY>
+where
+    M: RawMutex,
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Runs `operation` with the bus to itself, so no other sensor's packages get in between.
+    /// Only the addressed module answers, and the driver checks the address of every acknowledge packet.
+    /// The bus is given back when `operation` returns or the future is dropped. If an answer may still be on its way then,
+    /// for example because the operation failed half way or was dropped, whichever sensor takes the bus next recovers it before its first command.
+    pub async fn exclusive<T>(
+        &mut self,
+        operation: impl AsyncFnOnce(&mut Self) -> Result<T, Error<UART::Error>>,
+    ) -> Result<T, Error<UART::Error>> {
+        let mut line = self.uart.bus.line.lock().await;
+        if core::mem::take(&mut line.unsettled) {
+            self.pending = true;
+        }
+        self.uart.guard = Some(line);
+
+        let release = Release(self);
+        operation(&mut *release.0).await
+    }
+}
+
+/// Gives the bus back when `exclusive` returns or its future is dropped.
+struct Release<'d, 'a, M: RawMutex, UART, DELAY>(&'d mut Driver<BusUart<'a, M, UART>, DELAY>);
+
+impl<M: RawMutex, UART, DELAY> Drop for Release<'_, '_, M, UART, DELAY> {
+    fn drop(&mut self) {
+        if let Some(mut line) = self.0.uart.guard.take() {
+            line.unsettled |= self.0.pending;
+        }
+    }
+}
@@ -0,0 +1,72 @@
+//! Writes fingerprint images to files, for support tickets and for tuning algorithms on the host.
+//! Every format that has room for it records the sensor's resolution, 508 dpi.
+
+use std::io::{self, Write};
+
+use crate::{ImageData, IMAGE_SIDE};
+
+/// Image resolution: 508dpi
+pub const DPI: u32 = 508;
+/// 508 dpi in pixels per meter, the unit BMP and PNG record it in.
+const PIXELS_PER_METER: u32 = 20000;
+/// `IMAGE_SIDE`, the way the file headers record it.
+const SIDE: u32 = IMAGE_SIDE as u32;
+
+/// Binary PGM (P5). The format has no field for the resolution, so it goes in a comment.
+pub fn write_pgm(image: &ImageData, mut out: impl Write) -> io::Result<()> {
+    write!(out, "P5\n# {DPI} dpi\n{SIDE} {SIDE}\n255\n")?;
+    out.write_all(image)
+}
+
+/// 8-bit BMP with a grayscale palette, as the manual suggests for images uploaded with UpImage.
+pub fn write_bmp(image: &ImageData, mut out: impl Write) -> io::Result<()> {
+    // File header, info header and palette.
+    const OFFSET: u32 = 14 + 40 + 256 * 4;
+
+    out.write_all(b"BM")?;
+    out.write_all(&(OFFSET + SIDE * SIDE).to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&OFFSET.to_le_bytes())?;
+
+    out.write_all(&40u32.to_le_bytes())?;
+    out.write_all(&SIDE.to_le_bytes())?;
+    out.write_all(&SIDE.to_le_bytes())?;
+    // Planes, bits per pixel.
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&8u16.to_le_bytes())?;
+    // No compression.
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&(SIDE * SIDE).to_le_bytes())?;
+    out.write_all(&PIXELS_PER_METER.to_le_bytes())?;
+    out.write_all(&PIXELS_PER_METER.to_le_bytes())?;
+    // Colors used, all of them important.
+    out.write_all(&256u32.to_le_bytes())?;
+    out.write_all(&0u32.to_le_bytes())?;
+
+    for level in 0..=255u8 {
+        out.write_all(&[level, level, level, 0])?;
+    }
+
+    // Bottom row first. 192 bytes per row need no padding to 4 bytes.
+    for row in image.chunks_exact(SIDE as usize).rev() {
+        out.write_all(row)?;
+    }
+    Ok(())
+}
+
+/// 8-bit grayscale PNG, with the resolution in a pHYs chunk.
+#[cfg(feature = "png")]
+pub fn write_png(image: &ImageData, out: impl Write) -> Result<(), png::EncodingError> {
+    let mut encoder = png::Encoder::new(out, SIDE, SIDE);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_pixel_dims(Some(png::PixelDimensions {
+        xppu: PIXELS_PER_METER,
+        yppu: PIXELS_PER_METER,
+        unit: png::Unit::Meter,
+    }));
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(image)?;
+    writer.finish()
+}
@@ -1,12 +1,48 @@
 #![no_std]
-use core::mem::size_of;
-use embedded_io_async::{Read, Write};
-use heapless::Vec;
+#[cfg(feature = "std")]
+extern crate std;
 
-pub struct Driver<UART> {
+use core::mem::size_of;
+use embassy_futures::select::{select, Either};
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read, ReadExactError, Write};
+use heapless::{String, Vec};
+
+#[cfg(feature = "std")]
+pub mod backup;
+pub mod bus;
+#[cfg(feature = "image-export")]
+pub mod export;
+pub mod quality;
+#[cfg(feature = "simulator")]
+pub mod simulator;
+
+pub struct Driver<UART, DELAY = NoDelay> {
     pub uart: UART,
 
-    packet: Package,
+    address: Address,
+    parser: Parser,
+    password: Password,
+    packet_length: PacketLength,
+    /// Data packet length the module keeps in flash, as last read. It takes effect when the module starts.
+    stored_packet_length: Option<PacketLength>,
+    baud_rate: BaudRate,
+    library_size: u16,
+    delay: Option<DELAY>,
+    timeout: Option<u32>,
+    /// A command was sent and the module may still be busy with it or sending its answer.
+    pending: bool,
+    /// The last command sent, so `recover` can tell its answer from the one it waits for.
+    last_instruction: Option<Instruction>,
+}
+
+/// Stands in for the delay of a `Driver` that has none: nothing times out.
+pub struct NoDelay;
+
+impl DelayNs for NoDelay {
+    async fn delay_ns(&mut self, _ns: u32) {
+        core::future::pending().await
+    }
 }
 
 // DOCUMENTATION: R503 https://github.com/adafruit/Adafruit-Fingerprint-Sensor-Library/files/10964885/R503.fingerprint.module.user.manual-V1.2.2.pdf
@@ -17,7 +53,7 @@ pub struct Driver<UART> {
 // Hangzhou Grow Technology Co., Ltd.
 // 2022.10 Ver: 1.2.2
 
-#[allow(dead_code)]
+/// The module sends this byte once it is ready to take commands, after power-on and after SoftRst.
 const READY: u8 = 0x55;
 
 // ## Preface & Declaration
@@ -36,15 +72,19 @@ const READY: u8 = 0x55;
 // Matching Time: 1:N<10ms/Fingerprint
 // Standby current (finger detection): Typical touch standby voltage: 3.3V Average current: 2uA
 /// Characteristic value size: 512 bytes
+#[allow(dead_code)]
 type CharacterData = [u8; 512];
 // Baud rate: (9600*N)bps, N=1~6 (default N=6)
 /// Template size: 1536 bytes
-#[allow(dead_code)]
-type TemplateData = [u8; 1536];
+pub type TemplateData = [u8; 1536];
 // Image acquiring time: <0.2s
 // Image resolution: 508dpi
-// Sensing Array: 192*192 pixel
-type ImageData = [u8; 192 * 192];
+/// Sensing Array: 192*192 pixel
+pub const IMAGE_SIDE: usize = 192;
+/// Image: 192*192 pixels, 256 gray levels
+pub type ImageData = [u8; IMAGE_SIDE * IMAGE_SIDE];
+/// UpImage and DownImage transfer 4 bits per pixel: 192*192/2 bytes
+pub const PACKED_IMAGE_SIZE: usize = IMAGE_SIDE * IMAGE_SIDE / 2;
 // Detection Area Diameter: 15mm
 // Storage capacity: 200
 // Security level: 5 (1, 2, 3, 4, 5(highest)) FAR <0.001% FRR <1%
@@ -152,7 +192,7 @@ pub enum ParameterSetting {
 /// # Baud rate control (Parameter Number: 4)
 /// The Parameter controls the UART communication speed of the Modul. Its value is an integer N, N= [1/2/4/6/12]. Cooresponding baud rate is 9600*N bps.
 #[repr(u8)]
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum BaudRate {
     Rate9600 = 1,
     Rate19200 = 2,
@@ -162,10 +202,26 @@ pub enum BaudRate {
     Rate115200 = 12,
 }
 
+impl BaudRate {
+    /// Every baud rate the module can be set to, slowest first.
+    pub const ALL: [BaudRate; 5] = [
+        BaudRate::Rate9600,
+        BaudRate::Rate19200,
+        BaudRate::Rate38400,
+        BaudRate::Rate57600,
+        BaudRate::Rate115200,
+    ];
+
+    /// Bits per second: 9600*N.
+    pub fn bps(self) -> u32 {
+        9600 * self as u32
+    }
+}
+
 /// # Security Level (Parameter Number: 5)
 /// The Parameter controls the matching threshold value of fingerprint searching and matching. Security level is divided into 5 grades, and cooresponding value is 1, 2, 3, 4, 5. At level 1, FAR is the highest and FRR is the lowest; however at level 5, FAR is the lowest and FRR is the highest.
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SecurityLevel {
     Level1 = 1,
     Level2 = 2,
@@ -177,7 +233,7 @@ pub enum SecurityLevel {
 /// # Data package length (Parameter Number: 6)
 /// The parameter decides the max length of the transferring data package when communicating with upper computer. Its value is 0, 1, 2, 3, corresponding to 32 bytes, 64 bytes, 128 bytes, 256 bytes respectively.
 #[repr(u8)]
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum PacketLength {
     Bytes32 = 0,
     Bytes64 = 1,
@@ -186,6 +242,27 @@ pub enum PacketLength {
     Bytes256 = 3,
 }
 
+impl PacketLength {
+    /// Number of data bytes carried by one data packet.
+    pub fn bytes(self) -> usize {
+        32 << self as usize
+    }
+}
+
+impl TryFrom<u16> for PacketLength {
+    type Error = u16;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Bytes32),
+            1 => Ok(Self::Bytes64),
+            2 => Ok(Self::Bytes128),
+            3 => Ok(Self::Bytes256),
+            _ => Err(value),
+        }
+    }
+}
+
 // # System status register
 // System status register indicates the current operation status of the Module. Its length is 1 word, and can be read via instruction ReadSysPara. Definition of the register is as follows:
 // Bit Number   Description Notes
@@ -195,26 +272,59 @@ pub enum PacketLength {
 // 1            Pass        1 = Find the matching finger; 0 = wrong finger;
 // 0            Busy        1 = Sstem is executing commands; 0 = system is free;
 
-#[repr(u16)]
-#[derive(Clone, Copy, Default, PartialEq, Eq)]
-pub enum SystemRegister {
-    Busy = 0b0000_0000_0000_0001,       // 0x01 (1)
-    Pass = 0b0000_0000_0000_0010,       // 0x02 (2)
-    Pwd = 0b0000_0000_0000_0100,        // 0x03 (3)
-    ImgBufStat = 0b0000_0000_0000_1000, // 0x04 (4)
-    #[default]
-    Reserved = 0b1111_1111_1111_0000,
-}
+/// The system status register, as a set of the flags above.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SystemRegister(u16);
 
 impl SystemRegister {
-    pub fn from(value: u16) -> Self {
-        match value {
-            0b0000_0000_0000_0001 => Self::Busy,
-            0b0000_0000_0000_0010 => Self::Pass,
-            0b0000_0000_0000_0100 => Self::Pwd,
-            0b0000_0000_0000_1000 => Self::ImgBufStat,
-            _ => Self::Reserved,
-        }
+    pub const BUSY: Self = Self(0b0000_0000_0000_0001);
+    pub const PASS: Self = Self(0b0000_0000_0000_0010);
+    pub const PWD: Self = Self(0b0000_0000_0000_0100);
+    pub const IMG_BUF_STAT: Self = Self(0b0000_0000_0000_1000);
+    pub const RESERVED: Self = Self(0b1111_1111_1111_0000);
+
+    /// The register as read, reserved bits included.
+    pub fn bits(self) -> u16 {
+        self.0
+    }
+
+    /// Whether all flags set in `flags` are set.
+    pub fn contains(self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    /// The module is executing commands.
+    pub fn is_busy(self) -> bool {
+        self.contains(Self::BUSY)
+    }
+
+    /// The last search or match found the finger.
+    pub fn pass(self) -> bool {
+        self.contains(Self::PASS)
+    }
+
+    /// The handshaking password is verified.
+    pub fn password_verified(self) -> bool {
+        self.contains(Self::PWD)
+    }
+
+    /// The ImageBuffer holds a valid image.
+    pub fn image_buffer_valid(self) -> bool {
+        self.contains(Self::IMG_BUF_STAT)
+    }
+}
+
+impl From<u16> for SystemRegister {
+    fn from(value: u16) -> Self {
+        Self(value)
+    }
+}
+
+impl core::ops::BitOr for SystemRegister {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
     }
 }
 
@@ -271,7 +381,7 @@ pub const ADDRESS: Address = 0xFFFFFFFF;
 //      0x07: Acknowledge packet;
 //      0x08: End of Data packet.
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Identifier {
     /// 1 = Command Packet
     Command = 0x01,
@@ -288,6 +398,19 @@ impl From<Identifier> for u8 {
         item as u8
     }
 }
+impl TryFrom<u8> for Identifier {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Command),
+            0x02 => Ok(Self::Data),
+            0x07 => Ok(Self::Acknowledge),
+            0x08 => Ok(Self::End),
+            _ => Err(value),
+        }
+    }
+}
 
 /// Name: Package length
 /// Symbol: LENGTH
@@ -345,12 +468,12 @@ pub enum Payload {
     RegModel,
     UpChar(BufferID),
     DownChar(BufferID),
-    Store(BufferID, IndexPage),
-    LoadChar(BufferID, IndexPage),
-    DeleteChar(BufferID, u8),
+    Store(BufferID, ModelId),
+    LoadChar(BufferID, ModelId),
+    DeleteChar(ModelId, u16),
     Empty,
     Match,
-    Search(BufferID, u16, u16),
+    Search(BufferID, ModelId, u16),
     GetImageEx,
     Cancel,
     HandShake,
@@ -360,7 +483,7 @@ pub enum Payload {
     ReadProdInfo,
     SoftRst,
     AuraLedConfig(LightPattern, Speed, Color, Times),
-    AutoEnroll(IndexPage, bool, bool, bool, bool),
+    AutoEnroll(u8, bool, bool, bool, bool),
     AutoIdentify(SafeGrade, u8, u8, u8, bool),
     GetRandomCode,
     ReadInfPage,
@@ -387,12 +510,12 @@ impl Payload {
             Self::RegModel => size_of::<()>(),
             Self::UpChar(_) => size_of::<BufferID>(),
             Self::DownChar(_) => size_of::<BufferID>(),
-            Self::Store(_, _) => size_of::<(BufferID, IndexPage)>(),
-            Self::LoadChar(_, _) => size_of::<(BufferID, IndexPage)>(),
-            Self::DeleteChar(_, _) => size_of::<(BufferID, u8)>(),
+            Self::Store(_, _) => size_of::<(BufferID, ModelId)>(),
+            Self::LoadChar(_, _) => size_of::<(BufferID, ModelId)>(),
+            Self::DeleteChar(_, _) => size_of::<(ModelId, u16)>(),
             Self::Empty => size_of::<()>(),
             Self::Match => size_of::<()>(),
-            Self::Search(_, _, _) => size_of::<(BufferID, u16, u16)>(),
+            Self::Search(_, _, _) => size_of::<(BufferID, ModelId, u16)>(),
             Self::GetImageEx => size_of::<()>(),
             Self::Cancel => size_of::<()>(),
             Self::HandShake => size_of::<()>(),
@@ -402,7 +525,7 @@ impl Payload {
             Self::ReadProdInfo => size_of::<()>(),
             Self::SoftRst => size_of::<()>(),
             Self::AuraLedConfig(_, _, _, _) => size_of::<(LightPattern, Speed, Color, Times)>(),
-            Self::AutoEnroll(_, _, _, _, _) => size_of::<(IndexPage, bool, bool, bool, bool)>(),
+            Self::AutoEnroll(_, _, _, _, _) => size_of::<(u8, bool, bool, bool, bool)>(),
             Self::AutoIdentify(_, _, _, _, _) => size_of::<(SafeGrade, u8, u8, u8, bool)>(),
             Self::GetRandomCode => size_of::<()>(),
             Self::ReadInfPage => size_of::<()>(),
@@ -436,130 +559,138 @@ impl Payload {
         )
     }
 
-    fn as_bytes(&self) -> Vec<u8, 520> {
+    fn as_bytes(&self) -> Result<Vec<u8, 520>, PacketError> {
         let mut buffer = Vec::new();
 
         match self {
-            Payload::None => buffer,
+            Payload::None => {}
             Payload::VfyPwd(password) => {
                 let bytes = password.to_be_bytes();
-                let _ = buffer.extend_from_slice(&bytes);
-                buffer
-            },
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
             Payload::SetPwd(password) => {
                 let bytes = password.to_be_bytes();
-                let _ = buffer.extend_from_slice(&bytes);
-                buffer
-            },
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
             Payload::SetAdder(address) => {
                 let bytes = address.to_be_bytes();
-                let _ = buffer.extend_from_slice(&bytes);
-                buffer
-            },
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
             Payload::SetSysPara(parameter_setting, value) => {
-                let _ = buffer.push(*parameter_setting as u8);
-                let _ = buffer.push(*value as u8);
                 buffer
-            },
-            Payload::ReadSysPara => buffer,
-            Payload::TempleteNum => buffer,
+                    .push(*parameter_setting as u8)
+                    .map_err(buffer_overflow)?;
+                buffer.push(*value).map_err(buffer_overflow)?;
+            }
+            Payload::ReadSysPara => {}
+            Payload::TempleteNum => {}
             Payload::ReadIndexTable(index_page) => {
-                let _ = buffer.push(*index_page as u8);
-                buffer
-            },
-            Payload::GenImg => buffer,
+                buffer.push(*index_page as u8).map_err(buffer_overflow)?;
+            }
+            Payload::GenImg => {}
             Payload::Img2Tz(buffer_id) => {
-                let _ = buffer.push(*buffer_id as u8);
-                buffer
-            },
-            Payload::UpImage => buffer,
-            Payload::DownImage => buffer,
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
+            }
+            Payload::UpImage => {}
+            Payload::DownImage => {}
             Payload::GenChar(buffer_id) => {
-                let _ = buffer.push(*buffer_id as u8);
-                buffer
-            },
-            Payload::RegModel => buffer,
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
+            }
+            Payload::RegModel => {}
             Payload::UpChar(buffer_id) => {
-                let _ = buffer.push(*buffer_id as u8);
-                buffer
-            },
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
+            }
             Payload::DownChar(buffer_id) => {
-                let _ = buffer.push(*buffer_id as u8);
-                buffer
-            },
-            Payload::Store(buffer_id, index_page) => {
-                let _ = buffer.push(*buffer_id as u8);
-                let _ = buffer.push(*index_page as u8);
-                buffer
-            },
-            Payload::LoadChar(buffer_id, index_page) => {
-                let _ = buffer.push(*buffer_id as u8);
-                let _ = buffer.push(*index_page as u8);
-                buffer
-            },
-            Payload::DeleteChar(buffer_id, num) => {
-                let _ = buffer.push(*buffer_id as u8);
-                let _ = buffer.push(*num);
-                buffer
-            },
-            Payload::Empty => buffer,
-            Payload::Match => buffer,
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
+            }
+            Payload::Store(buffer_id, model_id) => {
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
+
+                let bytes = model_id.get().to_be_bytes();
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
+            Payload::LoadChar(buffer_id, model_id) => {
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
+
+                let bytes = model_id.get().to_be_bytes();
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
+            Payload::DeleteChar(start_id, num) => {
+                let bytes = start_id.get().to_be_bytes();
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+
+                let bytes = num.to_be_bytes();
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
+            Payload::Empty => {}
+            Payload::Match => {}
             Payload::Search(buffer_id, start_id, num) => {
-                let _ = buffer.push(*buffer_id as u8);
+                buffer.push(*buffer_id as u8).map_err(buffer_overflow)?;
 
-                let bytes = start_id.to_be_bytes();
-                let _ = buffer.extend_from_slice(&bytes);
+                let bytes = start_id.get().to_be_bytes();
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
 
                 let bytes = num.to_be_bytes();
-                let _ = buffer.extend_from_slice(&bytes);
-
-                buffer
-            },
-            Payload::GetImageEx => buffer,
-            Payload::Cancel => buffer,
-            Payload::HandShake => buffer,
-            Payload::CheckSensor => buffer,
-            Payload::GetAlgVer => buffer,
-            Payload::GetFwVer => buffer,
-            Payload::ReadProdInfo => buffer,
-            Payload::SoftRst => buffer,
+                buffer.extend_from_slice(&bytes).map_err(buffer_overflow)?;
+            }
+            Payload::GetImageEx => {}
+            Payload::Cancel => {}
+            Payload::HandShake => {}
+            Payload::CheckSensor => {}
+            Payload::GetAlgVer => {}
+            Payload::GetFwVer => {}
+            Payload::ReadProdInfo => {}
+            Payload::SoftRst => {}
             Payload::AuraLedConfig(light_pattern, speed, color, times) => {
-                let _ = buffer.push(*light_pattern as u8);
-                let _ = buffer.push(*speed as u8);
-                let _ = buffer.push(*color as u8);
-                let _ = buffer.push(*times as u8);
+                buffer.push(*light_pattern as u8).map_err(buffer_overflow)?;
+                buffer.push(*speed).map_err(buffer_overflow)?;
+                buffer.push(*color as u8).map_err(buffer_overflow)?;
+                buffer.push(*times).map_err(buffer_overflow)?;
+            }
+            Payload::AutoEnroll(
+                model_id,
+                allow_cover_id,
+                allow_duplicate,
+                return_in_critical,
+                ask_finger_to_leave,
+            ) => {
+                buffer.push(*model_id).map_err(buffer_overflow)?;
                 buffer
-            },
-            Payload::AutoEnroll(index_page, allow_cover_id,allow_duplicate, return_in_critical, ask_finger_to_leave) => {
-                let _ = buffer.push(*index_page as u8);
-                let _ = buffer.push(*allow_cover_id as u8);
-                let _ = buffer.push(*allow_duplicate as u8);
-                let _ = buffer.push(*return_in_critical as u8);
-                let _ = buffer.push(*ask_finger_to_leave as u8);
+                    .push(*allow_cover_id as u8)
+                    .map_err(buffer_overflow)?;
                 buffer
-            },
+                    .push(*allow_duplicate as u8)
+                    .map_err(buffer_overflow)?;
+                buffer
+                    .push(*return_in_critical as u8)
+                    .map_err(buffer_overflow)?;
+                buffer
+                    .push(*ask_finger_to_leave as u8)
+                    .map_err(buffer_overflow)?;
+            }
             Payload::AutoIdentify(safe_grade, start, num, times, return_in_critical) => {
-                let _ = buffer.push(*safe_grade as u8);
-                let _ = buffer.push(*start);
-                let _ = buffer.push(*num);
-                let _ = buffer.push(*times);
-                let _ = buffer.push(*return_in_critical as u8);
+                buffer.push(*safe_grade as u8).map_err(buffer_overflow)?;
+                buffer.push(*start).map_err(buffer_overflow)?;
+                buffer.push(*num).map_err(buffer_overflow)?;
+                buffer.push(*times).map_err(buffer_overflow)?;
                 buffer
-            },
-            Payload::GetRandomCode => buffer,
-            Payload::ReadInfPage => buffer,
+                    .push(*return_in_critical as u8)
+                    .map_err(buffer_overflow)?;
+            }
+            Payload::GetRandomCode => {}
+            Payload::ReadInfPage => {}
             Payload::WriteNotepad(note_page_num, page) => {
-                let _ = buffer.push(*note_page_num as u8);
+                buffer.push(*note_page_num).map_err(buffer_overflow)?;
                 for byte in &page[..] {
-                    let _ = buffer.push(*byte);
+                    buffer.push(*byte).map_err(buffer_overflow)?;
                 }
-                buffer
-            },
+            }
             Payload::ReadNotepad(note_page_num) => {
-                let _ = buffer.push(*note_page_num as u8);
-                buffer
-            },
+                buffer.push(*note_page_num).map_err(buffer_overflow)?;
+            }
         }
+
+        Ok(buffer)
     }
 }
 
@@ -609,17 +740,20 @@ impl Package {
     }
 
     /// Sets the length of the package in bytes, and returns the same.
-    pub fn len(&mut self) -> Length {
-        let mut length: Length = 0;
-        length += size_of::<Instruction>() as u16;
-        length += self.contents.payload.as_bytes().len() as u16;
-        length += size_of::<Sum>() as u16;
+    /// There's no `is_empty`: a package holds at least the instruction and the checksum.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&mut self) -> Result<Length, PacketError> {
+        let mut length = size_of::<Instruction>();
+        length += self.contents.payload.as_bytes()?.len();
+        length += size_of::<Sum>();
 
         // Yep. It's a u16, but has to be less than or equal to 256 bytes.
-        assert!(length <= 256);
+        if length > 256 {
+            return Err(PacketError::Length(length));
+        }
 
-        self.length = length;
-        length
+        self.length = length as Length;
+        Ok(self.length)
     }
 
     /// Get Contents
@@ -633,28 +767,32 @@ impl Package {
 
     /// Calculates the checksum of all of the bytes in the Package.
     /// Sets the checksum, and returns the same.
-    pub fn checksum(&mut self) -> Sum {
+    pub fn checksum(&mut self) -> Result<Sum, PacketError> {
         let mut checksum: Sum = 0;
 
         // Identifier
-        checksum += self.identifier as u16;
+        checksum = checksum.wrapping_add(self.identifier as u16);
         // Length
-        checksum += get_u16_as_u16_parts(self.length)[0];
-        checksum += get_u16_as_u16_parts(self.length)[1];
-        // TODO: Contents
-        checksum += self.contents.instruction as u16;
-        for byte in self.contents.payload.as_bytes() {
-            checksum += byte as u16;
+        checksum = checksum.wrapping_add(get_u16_as_u16_parts(self.length)[0]);
+        checksum = checksum.wrapping_add(get_u16_as_u16_parts(self.length)[1]);
+        // Contents
+        checksum = checksum.wrapping_add(self.contents.instruction as u16);
+        for byte in self.contents.payload.as_bytes()? {
+            checksum = checksum.wrapping_add(byte as u16);
         }
         self.checksum = checksum;
-        checksum
+        Ok(checksum)
     }
     pub fn get_checksum(&self) -> Sum {
         self.checksum
     }
 
     /// Building function to quickly get the bytes setup correct.
-    pub fn build(identifier: Identifier, instruction: Instruction, payload: Payload) -> Self {
+    pub fn build(
+        identifier: Identifier,
+        instruction: Instruction,
+        payload: Payload,
+    ) -> Result<Self, PacketError> {
         let mut package = Self {
             identifier,
             contents: Data {
@@ -663,47 +801,39 @@ impl Package {
             },
             ..Package::default()
         };
-        package.len();
-        package.checksum();
+        package.len()?;
+        package.checksum()?;
 
-        package
+        Ok(package)
     }
 
     /// Get the bytes of the struct cleanly.
-    pub fn as_bytes(&mut self) -> Vec<u8, 528> {
+    pub fn as_bytes(&mut self) -> Result<Vec<u8, 528>, PacketError> {
         let mut bytes = Vec::new();
 
         // Header
         let data = self.header.to_be_bytes();
-        for byte in data {
-            let _ = bytes.push(byte);
-        }
+        bytes.extend_from_slice(&data).map_err(buffer_overflow)?;
         // Address
         let data = self.address.to_be_bytes();
-        for byte in data {
-            let _ = bytes.push(byte);
-        }
+        bytes.extend_from_slice(&data).map_err(buffer_overflow)?;
         // Identifier
-        let _ = bytes.push(self.identifier as u8);
+        bytes.push(self.identifier as u8).map_err(buffer_overflow)?;
         // Length
-        let data = self.len().to_be_bytes();
-        for byte in data {
-            let _ = bytes.push(byte);
-        }
+        let data = self.len()?.to_be_bytes();
+        bytes.extend_from_slice(&data).map_err(buffer_overflow)?;
         // Contents - Instruction
-        let _ = bytes.push(self.contents.instruction as u8);
+        bytes
+            .push(self.contents.instruction as u8)
+            .map_err(buffer_overflow)?;
         // Contents - Payload
-        let data = self.contents.payload.as_bytes();
-        for byte in data {
-            let _ = bytes.push(byte);
-        }
+        let data = self.contents.payload.as_bytes()?;
+        bytes.extend_from_slice(&data).map_err(buffer_overflow)?;
         // Checksum
-        let data = self.checksum().to_be_bytes();
-        for byte in data {
-            let _ = bytes.push(byte);
-        }
+        let data = self.checksum()?.to_be_bytes();
+        bytes.extend_from_slice(&data).map_err(buffer_overflow)?;
 
-        bytes
+        Ok(bytes)
     }
 }
 
@@ -720,6 +850,217 @@ impl Default for Package {
     }
 }
 
+/// A package as it was received on the wire, with the checksum already verified.
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    /// See Name: Adder
+    pub address: Address,
+    /// See Name: Package identifier
+    pub identifier: Identifier,
+    /// See Name: Package contents, without the checksum.
+    pub contents: Vec<u8, 256>,
+}
+
+impl Frame {
+    /// The confirmation code of an acknowledge packet (the first byte of the contents).
+    pub fn confirmation_code(&self) -> ConfirmationCode {
+        match self.contents.first() {
+            Some(code) => ConfirmationCode::from(*code),
+            None => ConfirmationCode::default(),
+        }
+    }
+
+    /// The returned parameters of an acknowledge packet (everything after the confirmation code).
+    pub fn parameters(&self) -> &[u8] {
+        self.contents.get(1..).unwrap_or_default()
+    }
+
+    /// Get the bytes of the frame, the way `Parser` expects them.
+    pub fn as_bytes(&self) -> Result<Vec<u8, FRAME_MAX>, PacketError> {
+        let length = (self.contents.len() + size_of::<Sum>()) as Length;
+
+        let mut bytes = Vec::new();
+        bytes
+            .extend_from_slice(&HEADER.to_be_bytes())
+            .map_err(buffer_overflow)?;
+        bytes
+            .extend_from_slice(&self.address.to_be_bytes())
+            .map_err(buffer_overflow)?;
+        bytes.push(self.identifier as u8).map_err(buffer_overflow)?;
+        bytes
+            .extend_from_slice(&length.to_be_bytes())
+            .map_err(buffer_overflow)?;
+        bytes
+            .extend_from_slice(&self.contents)
+            .map_err(buffer_overflow)?;
+
+        // The checksum covers the package identifier, the package length and the contents.
+        let mut checksum: Sum = 0;
+        for byte in &bytes[6..] {
+            checksum = checksum.wrapping_add(*byte as Sum);
+        }
+        bytes
+            .extend_from_slice(&checksum.to_be_bytes())
+            .map_err(buffer_overflow)?;
+
+        Ok(bytes)
+    }
+}
+
+/// Reasons the parser threw a package away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// The package was addressed to some other module.
+    Address(Address),
+    /// The package identifier isn't one of 0x01, 0x02, 0x07 or 0x08.
+    Identifier(u8),
+    /// The package length can't hold a checksum, or is over the 256 bytes of contents.
+    Length(Length),
+    /// The checksum doesn't add up.
+    Checksum { expected: Sum, received: Sum },
+}
+
+/// Header (2 bytes) + Adder (4 bytes) + Package identifier (1 byte) + Package length (2 bytes)
+const FRAME_HEAD: usize = 9;
+/// A whole package with 256 bytes of contents.
+const FRAME_MAX: usize = FRAME_HEAD + 256 + size_of::<Sum>();
+
+/// Streaming decoder for packages coming from the module.
+/// Bytes are pushed in one at a time. Anything that isn't part of a package (like the 0x55 ready byte)
+/// is skipped until the parser finds the next 0xEF01 header.
+pub struct Parser {
+    address: Address,
+    /// Also accepted, while the module may be switching to it.
+    next_address: Option<Address>,
+    buffer: Vec<u8, FRAME_MAX>,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Self::new(ADDRESS)
+    }
+}
+
+impl Parser {
+    /// Only packages sent with this `address` are accepted.
+    pub fn new(address: Address) -> Self {
+        Self {
+            address,
+            next_address: None,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Accepts packages sent with `address` as well, until the parser is replaced.
+    pub(crate) fn accept_next_address(&mut self, address: Address) {
+        self.next_address = Some(address);
+    }
+
+    /// How many bytes can be pushed without going past the end of the current package.
+    /// Reading exactly this many bytes from the UART never swallows the start of the next package.
+    pub fn wants(&self) -> usize {
+        if self.buffer.len() < FRAME_HEAD {
+            return FRAME_HEAD - self.buffer.len();
+        }
+
+        FRAME_HEAD + self.length() as usize - self.buffer.len()
+    }
+
+    /// Throws away whatever was received so far.
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Pushes one byte. Returns a result once a whole package was received or rejected.
+    pub fn push(&mut self, byte: u8) -> Option<Result<Frame, FrameError>> {
+        let header = HEADER.to_be_bytes();
+
+        // Resynchronize on the header.
+        if self.buffer.len() < header.len() && byte != header[self.buffer.len()] {
+            self.buffer.clear();
+            if byte != header[0] {
+                return None;
+            }
+        }
+        if self.buffer.push(byte).is_err() {
+            let length = self.length();
+            self.buffer.clear();
+            return Some(Err(FrameError::Length(length)));
+        }
+
+        if self.buffer.len() == FRAME_HEAD {
+            if let Err(error) = self.check_head() {
+                self.buffer.clear();
+                return Some(Err(error));
+            }
+        }
+
+        if self.buffer.len() < FRAME_HEAD || self.wants() > 0 {
+            return None;
+        }
+
+        let frame = self.finish();
+        self.buffer.clear();
+        Some(frame)
+    }
+
+    fn length(&self) -> Length {
+        Length::from_be_bytes([self.buffer[7], self.buffer[8]])
+    }
+
+    /// The address the package came from, as received.
+    fn address(&self) -> Address {
+        Address::from_be_bytes([
+            self.buffer[2],
+            self.buffer[3],
+            self.buffer[4],
+            self.buffer[5],
+        ])
+    }
+
+    fn check_head(&self) -> Result<(), FrameError> {
+        let address = self.address();
+        if address != self.address && Some(address) != self.next_address {
+            return Err(FrameError::Address(address));
+        }
+
+        Identifier::try_from(self.buffer[6]).map_err(FrameError::Identifier)?;
+
+        let length = self.length();
+        let contents = (length as usize).saturating_sub(size_of::<Sum>());
+        if contents == 0 || FRAME_HEAD + length as usize > self.buffer.capacity() {
+            return Err(FrameError::Length(length));
+        }
+
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<Frame, FrameError> {
+        let (body, checksum) = self.buffer.split_at(self.buffer.len() - size_of::<Sum>());
+        let received = Sum::from_be_bytes([checksum[0], checksum[1]]);
+
+        // The checksum covers the package identifier, the package length and the contents.
+        let mut expected: Sum = 0;
+        for byte in &body[6..] {
+            expected = expected.wrapping_add(*byte as Sum);
+        }
+        if expected != received {
+            return Err(FrameError::Checksum { expected, received });
+        }
+
+        let mut contents = Vec::new();
+        contents
+            .extend_from_slice(&body[FRAME_HEAD..])
+            .map_err(|_| FrameError::Length(self.length()))?;
+
+        Ok(Frame {
+            address: self.address(),
+            identifier: Identifier::try_from(body[6]).map_err(FrameError::Identifier)?,
+            contents,
+        })
+    }
+}
+
 type NotePageNum = u8;
 
 // # Check and acknowledgement of data package
@@ -728,7 +1069,7 @@ type NotePageNum = u8;
 // Confirmation code's definition is:
 
 #[repr(u8)]
-#[derive(Debug, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub enum ConfirmationCode {
     /// 0 = Commad Execution Complete;
     Success = 0x00,
@@ -848,85 +1189,608 @@ impl ConfirmationCode {
     }
 }
 
+/// An acknowledge packet as received from the module.
+#[derive(Debug, PartialEq)]
+pub struct Acknowledge {
+    /// The first byte of the package contents.
+    pub confirmation_code: ConfirmationCode,
+    /// Whatever the instruction returns after the confirmation code.
+    pub parameters: Vec<u8, 256>,
+}
+
+/// Errors that can happen while talking to the module.
+#[derive(Debug, PartialEq)]
+pub enum Error<E> {
+    /// The UART itself failed.
+    Io(E),
+    /// The UART ran out of bytes in the middle of a package.
+    UnexpectedEof,
+    /// The module sent a package that couldn't be decoded.
+    Frame(FrameError),
+    /// The module sent a different kind of package than expected.
+    UnexpectedIdentifier(Identifier),
+    /// The data doesn't fit in the buffer it has to go into.
+    BufferOverflow,
+    /// The module answered with anything but `ConfirmationCode::Success`.
+    Confirmation(ConfirmationCode),
+    /// The acknowledge packet is too short for the parameters the instruction returns.
+    MissingParameters,
+    /// An argument is outside the range the module or the routine accepts.
+    InvalidArgument,
+    /// Gave up waiting: for the finger, or for the module to answer within the timeout.
+    Timeout,
+    /// The module reads back a different system parameter than was just written.
+    ReadBack,
+    /// The index table and TempleteNum disagree on the number of templates.
+    IndexMismatch,
+    /// The data packets ended before all of the image or template arrived.
+    IncompleteData,
+    /// `provision` failed, and so did setting the previous credentials again: the module's address and password are unknown.
+    CredentialsUnknown,
+}
+
+impl<E> From<ReadExactError<E>> for Error<E> {
+    fn from(error: ReadExactError<E>) -> Self {
+        match error {
+            ReadExactError::UnexpectedEof => Self::UnexpectedEof,
+            ReadExactError::Other(error) => Self::Io(error),
+        }
+    }
+}
+
+impl<E> From<PacketError> for Error<E> {
+    fn from(error: PacketError) -> Self {
+        match error {
+            PacketError::Overflow | PacketError::Length(_) => Self::BufferOverflow,
+        }
+    }
+}
+
+/// Errors building a package to send.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PacketError {
+    /// The contents don't fit in a package.
+    Overflow,
+    /// The package would be longer than the 256 bytes a package may have.
+    Length(usize),
+}
+
+#[inline]
+fn buffer_overflow<T>(_: T) -> PacketError {
+    PacketError::Overflow
+}
+
+async fn read_frame<UART: Read>(
+    uart: &mut UART,
+    parser: &mut Parser,
+) -> Result<Frame, Error<UART::Error>> {
+    let mut buffer = [0u8; 32];
+    loop {
+        // Never read more than the parser wants, so no byte of the next package gets lost.
+        let wants = parser.wants().min(buffer.len());
+        let chunk = &mut buffer[..wants];
+        uart.read_exact(chunk).await?;
+
+        for byte in chunk.iter() {
+            if let Some(frame) = parser.push(*byte) {
+                return frame.map_err(Error::Frame);
+            }
+        }
+    }
+}
+
 // # 5. Module Instruction System
 // R30X series provide 23 instructions. R50X series provide 33 instructions. Through combination of different instructions, application program may realize muti finger authentication functions. All commands/data are transferred in package format. Refer to 5.1 for the detailed information of package.
 
+/// How long `Driver::recover` waits for each package before it gives up, in ms.
+const RECOVER_TIMEOUT: u32 = 200;
+
 impl<UART> Driver<UART>
 where
     UART: Read + Write,
 {
-    /// You must provide a `uart` that can read and write.
-    /// You can provide an address, or it will use the default address.
-    pub fn new(&mut self, uart: UART, address: Option<Address>) -> &mut Self {
-        self.uart = uart;
-        self.packet.address = match address {
-            Some(address) => address,
-            None => ADDRESS,
-        };
-        self
+    /// A driver for a module with factory settings on `uart`. See `builder` for anything else.
+    pub fn new(uart: UART) -> Self {
+        Self::builder(uart).build()
     }
 
-    // # System-related instructions
+    /// Configures a driver for `uart`.
+    pub fn builder(uart: UART) -> DriverBuilder<UART> {
+        DriverBuilder {
+            uart,
+            delay: None,
+            timeout: None,
+            address: ADDRESS,
+            password: PASSWORD,
+            // The value is 128 Bytes before delivery.
+            packet_length: PacketLength::Bytes128,
+            baud_rate: BaudRate::default(),
+        }
+    }
 
-    /// Verify passwoard - VfyPwd
-    /// Description: Verify Module's handshaking password.
-    /// Input Parameter: Password (4 bytes)
-    /// Return Parameter: Confirmation code (1 byte)
-    ///     0x00: Correct password;
-    ///     0x01: Error when receiving package;
-    ///     0x13: Wrong password;
-    /// Instruction code: 0x13
-    pub fn vfy_pwd(&mut self, password: Option<Password>) -> Package {
-        let package = Package::build(
-            Identifier::Command,
-            Instruction::VfyPwd,
-            Payload::VfyPwd(match password {
-                Some(password) => password,
-                None => PASSWORD,
-            }),
-        );
+    /// Gives the driver a timer, so waiting for the module can time out. See `set_timeout`.
+    pub fn with_delay<DELAY: DelayNs>(self, delay: DELAY) -> Driver<UART, DELAY> {
+        Driver {
+            uart: self.uart,
+            address: self.address,
+            parser: self.parser,
+            password: self.password,
+            packet_length: self.packet_length,
+            stored_packet_length: self.stored_packet_length,
+            baud_rate: self.baud_rate,
+            library_size: self.library_size,
+            delay: Some(delay),
+            timeout: self.timeout,
+            pending: self.pending,
+            last_instruction: self.last_instruction,
+        }
+    }
+}
+
+/// Settings for a `Driver`, matching how the module is configured. Start with `Driver::builder`.
+pub struct DriverBuilder<UART, DELAY = NoDelay> {
+    uart: UART,
+    delay: Option<DELAY>,
+    timeout: Option<u32>,
+    address: Address,
+    password: Password,
+    packet_length: PacketLength,
+    baud_rate: BaudRate,
+}
 
-        package
+impl<UART, DELAY> DriverBuilder<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Timer for timeouts, see `Driver::set_timeout`.
+    pub fn delay<D: DelayNs>(self, delay: D) -> DriverBuilder<UART, D> {
+        DriverBuilder {
+            uart: self.uart,
+            delay: Some(delay),
+            timeout: self.timeout,
+            address: self.address,
+            password: self.password,
+            packet_length: self.packet_length,
+            baud_rate: self.baud_rate,
+        }
     }
 
-    /// Set password - SetPwd
-    /// Description: Set Module's handshaking password.
-    /// Input Parameter: Password (4 bytes)
-    /// Return Parameter: Confirmation code (1 byte)
-    ///     0x00: Password setting complete;
-    ///     0x01: Error when receiving package;
-    ///     0x18: Error when writing FLASH;
-    ///     0x21: Have to verify password;
-    /// Instruction code: 0x12
-    pub fn set_pwd(&mut self, password: Option<Password>) -> Package {
-        let package = Package::build(
-            Identifier::Command,
-            Instruction::SetPwd,
-            Payload::SetPwd(match password {
-                Some(password) => password,
-                None => PASSWORD,
-            }),
-        );
+    /// See `Driver::set_timeout`.
+    pub fn timeout(mut self, timeout: u32) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 
-        package
+    /// The module's address, set with SetAdder. Defaults to `ADDRESS`.
+    pub fn address(mut self, address: Address) -> Self {
+        self.address = address;
+        self
     }
 
-    /// Set Module address - SetAdder
-    /// Description: Set Module address.
-    /// Input Parameter: Addr
-    /// Return Parameter: Confirmation code (1 byte)
-    ///     0x00: Address setting complete;
-    ///     0x01: Error when receiving package;
-    ///     0x18: Error when writing FLASH;
+    /// The module's password, set with SetPwd. `Driver::init` verifies it when it isn't the default one.
+    pub fn password(mut self, password: Password) -> Self {
+        self.password = password;
+        self
+    }
+
+    /// The module's data packet length. Defaults to 128 bytes, as delivered; `Driver::init` reads the actual one.
+    pub fn packet_length(mut self, packet_length: PacketLength) -> Self {
+        self.packet_length = packet_length;
+        self
+    }
+
+    /// The baud rate the UART is set up with. Defaults to 57600 bps, as delivered.
+    pub fn baud_rate(mut self, baud_rate: BaudRate) -> Self {
+        self.baud_rate = baud_rate;
+        self
+    }
+
+    pub fn build(self) -> Driver<UART, DELAY> {
+        Driver {
+            uart: self.uart,
+            address: self.address,
+            parser: Parser::new(self.address),
+            password: self.password,
+            packet_length: self.packet_length,
+            stored_packet_length: None,
+            baud_rate: self.baud_rate,
+            // Storage capacity: 200
+            library_size: 200,
+            delay: self.delay,
+            timeout: self.timeout,
+            pending: false,
+            last_instruction: None,
+        }
+    }
+}
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// How long to wait for each package from the module, in ms, before giving up with `Error::Timeout`.
+    /// `None` waits forever, and so does a driver without a delay.
+    /// Keep in mind that AutoEnroll and AutoIdentify wait up to 10 seconds for the finger before they answer.
+    pub fn set_timeout(&mut self, timeout: Option<u32>) {
+        self.timeout = timeout;
+    }
+
+    pub fn timeout(&self) -> Option<u32> {
+        self.timeout
+    }
+
+    /// Runs `operation` with a different timeout, see `set_timeout`.
+    pub async fn with_timeout<T>(
+        &mut self,
+        timeout: u32,
+        operation: impl AsyncFnOnce(&mut Self) -> Result<T, Error<UART::Error>>,
+    ) -> Result<T, Error<UART::Error>> {
+        let previous = self.timeout.replace(timeout);
+        let result = operation(self).await;
+        self.timeout = previous;
+        result
+    }
+
+    /// Brings the module and the port back to a known state after an operation timed out or was dropped before it finished:
+    /// sends Cancel and then ReadSysPara, and throws away everything the module sends up to the answer to ReadSysPara,
+    /// which no other instruction answers with. After a dropped ReadSysPara, ReadProdInfo takes its place.
+    /// Gives up with `Error::Timeout` when the answer doesn't come, and tries again before the next command.
+    /// The driver calls this by itself: when waiting times out, and before the next command when an operation was dropped.
+    pub async fn recover(&mut self) -> Result<(), Error<UART::Error>> {
+        self.parser.reset();
+        let (instruction, payload, parameters) = match self.last_instruction {
+            Some(Instruction::ReadSysPara) => {
+                (Instruction::ReadProdInfo, Payload::ReadProdInfo, 46)
+            }
+            _ => (Instruction::ReadSysPara, Payload::ReadSysPara, 16),
+        };
+        let cancel = Package::build(Identifier::Command, Instruction::Cancel, Payload::Cancel)?;
+        self.write_package(cancel).await?;
+        let sentinel = Package::build(Identifier::Command, instruction, payload)?;
+        self.write_package(sentinel).await?;
+        // Should this answer come too late, the next `recover` waits for the other sentinel instead.
+        self.last_instruction = Some(instruction);
+
+        loop {
+            match self.read_frame(Some(RECOVER_TIMEOUT)).await {
+                Ok(Some(frame))
+                    if frame.identifier == Identifier::Acknowledge
+                        && frame.confirmation_code() == ConfirmationCode::Success
+                        && frame.parameters().len() == parameters =>
+                {
+                    break
+                }
+                Ok(Some(_)) | Err(Error::Frame(_)) => continue,
+                Ok(None) | Err(Error::UnexpectedEof) => return Err(Error::Timeout),
+                Err(error) => return Err(error),
+            }
+        }
+
+        self.parser.reset();
+        self.pending = false;
+        Ok(())
+    }
+
+    /// Waits for the module to be ready after power-on, up to `timeout` ms.
+    /// The module sends a ready byte, 0x55, once it is ready (starting time: =<50ms), and doesn't take commands before.
+    /// If the ready byte doesn't come, because it was sent before the driver started listening or because the driver has no delay to time out with,
+    /// asks the module instead: HandShake, then CheckSensor.
+    pub async fn wait_ready(&mut self, timeout: u32) -> Result<(), Error<UART::Error>> {
+        self.parser.reset();
+        if let Some(delay) = &mut self.delay {
+            let uart = &mut self.uart;
+            let ready = async {
+                let mut byte = [0u8];
+                loop {
+                    uart.read_exact(&mut byte).await?;
+                    if byte[0] == READY {
+                        return Ok::<_, Error<UART::Error>>(());
+                    }
+                }
+            };
+            if let Either::First(Ok(())) = select(ready, delay.delay_ms(timeout)).await {
+                self.adopt_packet_length();
+                return Ok(());
+            }
+        }
+
+        self.with_timeout(timeout, async |driver| match driver.handshake().await {
+            // The module is up, it only won't talk before the password is verified.
+            Err(Error::Confirmation(ConfirmationCode::MustVerifyPassword)) => Ok(()),
+            Ok(()) => driver.check_sensor().await,
+            Err(error) => Err(error),
+        })
+        .await?;
+        self.adopt_packet_length();
+        Ok(())
+    }
+
+    /// Brings the module up: waits for it to be ready, see `wait_ready`, verifies the password unless it's the default one,
+    /// then reads the system parameters, which also sets the packet length and library size the driver works with.
+    pub async fn init(&mut self, timeout: u32) -> Result<BasicParameters, Error<UART::Error>> {
+        self.wait_ready(timeout).await?;
+        let parameters = self.log_in(timeout).await?;
+        self.adopt_packet_length();
+        Ok(parameters)
+    }
+
+    /// Verifies the password unless it's the default one, then reads the system parameters.
+    async fn log_in(&mut self, timeout: u32) -> Result<BasicParameters, Error<UART::Error>> {
+        self.with_timeout(timeout, async |driver| {
+            if driver.password != PASSWORD {
+                driver.vfy_pwd(Some(driver.password)).await?;
+            }
+            driver.read_sys_para().await
+        })
+        .await
+    }
+
+    /// Data packet length used to split outgoing data, the one in effect on the module.
+    /// Follows the module's own setting when the module starts: `init`, `wait_ready` and `soft_rst`.
+    pub fn packet_length(&self) -> PacketLength {
+        self.packet_length
+    }
+
+    /// Puts the stored data packet length, if read already, into effect, as the module does when it starts.
+    fn adopt_packet_length(&mut self) {
+        if let Some(packet_length) = self.stored_packet_length {
+            self.packet_length = packet_length;
+        }
+    }
+
+    /// Address packages are sent to, and acknowledge packets are expected from.
+    /// Updated by `set_adder`.
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Password `init` verifies.
+    pub fn password(&self) -> Password {
+        self.password
+    }
+
+    /// Baud rate the UART is set up with.
+    pub fn baud_rate(&self) -> BaudRate {
+        self.baud_rate
+    }
+
+    /// Number of templates the fingerprint library holds, searched by `identify`.
+    /// Updated from the module every time `read_sys_para` is called.
+    pub fn library_size(&self) -> u16 {
+        self.library_size
+    }
+
+    /// Writes the command package to the UART and waits for the module's acknowledge packet.
+    async fn send(&mut self, package: Package) -> Result<Acknowledge, Error<UART::Error>> {
+        self.transmit(package).await?;
+        let acknowledge = self.receive_success().await;
+        self.finish(acknowledge)
+    }
+
+    /// Writes the command package to the UART, after cleaning up after an operation that didn't finish.
+    async fn transmit(&mut self, package: Package) -> Result<(), Error<UART::Error>> {
+        if self.pending {
+            self.recover().await?;
+        }
+
+        self.pending = true;
+        self.last_instruction = Some(package.contents.instruction);
+        self.write_package(package).await
+    }
+
+    async fn write_package(&mut self, mut package: Package) -> Result<(), Error<UART::Error>> {
+        package.set_address(self.address);
+        self.uart
+            .write_all(&package.as_bytes()?)
+            .await
+            .map_err(Error::Io)?;
+        self.uart.flush().await.map_err(Error::Io)
+    }
+
+    /// Reads one acknowledge packet, turning anything but `ConfirmationCode::Success` into an error.
+    async fn receive_success(&mut self) -> Result<Acknowledge, Error<UART::Error>> {
+        let acknowledge = self.receive().await?;
+        if acknowledge.confirmation_code != ConfirmationCode::Success {
+            return Err(Error::Confirmation(acknowledge.confirmation_code));
+        }
+
+        Ok(acknowledge)
+    }
+
+    /// Sends the command package and decodes the return parameters of its acknowledge packet.
+    async fn request<T: Response>(&mut self, package: Package) -> Result<T, Error<UART::Error>> {
+        self.transmit(package).await?;
+        let parameters = self.receive_parameters().await;
+        self.finish(parameters)
+    }
+
+    /// Marks the operation as finished when the module answered it, even with an error.
+    /// Any other error may leave packages on their way, so the next command recovers first.
+    fn finish<T>(
+        &mut self,
+        result: Result<T, Error<UART::Error>>,
+    ) -> Result<T, Error<UART::Error>> {
+        if matches!(result, Ok(_) | Err(Error::Confirmation(_))) {
+            self.pending = false;
+        }
+        result
+    }
+
+    /// Reads one acknowledge packet and decodes its return parameters.
+    async fn receive_parameters<T: Response>(&mut self) -> Result<T, Error<UART::Error>> {
+        let acknowledge = self.receive_success().await?;
+
+        T::decode(&acknowledge.parameters).ok_or(Error::MissingParameters)
+    }
+
+    /// Reads one acknowledge packet from the UART.
+    async fn receive(&mut self) -> Result<Acknowledge, Error<UART::Error>> {
+        let frame = self.receive_frame().await?;
+        if frame.identifier != Identifier::Acknowledge {
+            return Err(Error::UnexpectedIdentifier(frame.identifier));
+        }
+
+        let mut parameters = Vec::new();
+        parameters
+            .extend_from_slice(frame.parameters())
+            .map_err(buffer_overflow)?;
+
+        Ok(Acknowledge {
+            confirmation_code: frame.confirmation_code(),
+            parameters,
+        })
+    }
+
+    /// Sends `data` as data packets of at most `packet_length` bytes, the last one being an end of data packet.
+    async fn send_data(&mut self, data: &[u8]) -> Result<(), Error<UART::Error>> {
+        self.pending = true;
+        let mut chunks = data.chunks(self.packet_length.bytes()).peekable();
+        while let Some(chunk) = chunks.next() {
+            let frame = Frame {
+                address: self.address,
+                identifier: match chunks.peek() {
+                    Some(_) => Identifier::Data,
+                    None => Identifier::End,
+                },
+                contents: Vec::from_slice(chunk).map_err(buffer_overflow)?,
+            };
+            self.uart
+                .write_all(&frame.as_bytes()?)
+                .await
+                .map_err(Error::Io)?;
+        }
+        self.uart.flush().await.map_err(Error::Io)?;
+
+        self.finish(Ok(()))
+    }
+
+    /// Collects data packets into `buffer` until the end of data packet.
+    /// Returns the number of bytes received.
+    async fn receive_data(&mut self, buffer: &mut [u8]) -> Result<usize, Error<UART::Error>> {
+        self.pending = true;
+        let mut received = 0;
+        loop {
+            let frame = self.receive_frame().await?;
+            if !matches!(frame.identifier, Identifier::Data | Identifier::End) {
+                return Err(Error::UnexpectedIdentifier(frame.identifier));
+            }
+
+            let end = received + frame.contents.len();
+            buffer
+                .get_mut(received..end)
+                .ok_or(Error::BufferOverflow)?
+                .copy_from_slice(&frame.contents);
+            received = end;
+
+            if frame.identifier == Identifier::End {
+                return self.finish(Ok(received));
+            }
+        }
+    }
+
+    /// Reads one package of any kind from the UART.
+    /// When it doesn't come within the timeout, recovers and returns `Error::Timeout`.
+    async fn receive_frame(&mut self) -> Result<Frame, Error<UART::Error>> {
+        match self.read_frame(self.timeout).await? {
+            Some(frame) => Ok(frame),
+            None => {
+                self.recover().await?;
+                Err(Error::Timeout)
+            }
+        }
+    }
+
+    /// Reads one package of any kind from the UART, or `None` when it doesn't come within `timeout` ms.
+    async fn read_frame(
+        &mut self,
+        timeout: Option<u32>,
+    ) -> Result<Option<Frame>, Error<UART::Error>> {
+        let read = read_frame(&mut self.uart, &mut self.parser);
+        match (&mut self.delay, timeout) {
+            (Some(delay), Some(timeout)) => match select(read, delay.delay_ms(timeout)).await {
+                Either::First(frame) => frame.map(Some),
+                Either::Second(()) => Ok(None),
+            },
+            _ => read.await.map(Some),
+        }
+    }
+
+    // # System-related instructions
+
+    /// Verify passwoard - VfyPwd
+    /// Description: Verify Module's handshaking password.
+    /// Input Parameter: Password (4 bytes)
+    /// Return Parameter: Confirmation code (1 byte)
+    ///     0x00: Correct password;
+    ///     0x01: Error when receiving package;
+    ///     0x13: Wrong password;
+    /// Instruction code: 0x13
+    pub async fn vfy_pwd(&mut self, password: Option<Password>) -> Result<(), Error<UART::Error>> {
+        let package = Package::build(
+            Identifier::Command,
+            Instruction::VfyPwd,
+            Payload::VfyPwd(match password {
+                Some(password) => password,
+                None => PASSWORD,
+            }),
+        )?;
+
+        self.send(package).await?;
+        Ok(())
+    }
+
+    /// Set password - SetPwd
+    /// Description: Set Module's handshaking password.
+    /// Input Parameter: Password (4 bytes)
+    /// Return Parameter: Confirmation code (1 byte)
+    ///     0x00: Password setting complete;
+    ///     0x01: Error when receiving package;
+    ///     0x18: Error when writing FLASH;
+    ///     0x21: Have to verify password;
+    /// Instruction code: 0x12
+    /// Note: The driver verifies the new password from then on, see `init`.
+    pub async fn set_pwd(&mut self, password: Option<Password>) -> Result<(), Error<UART::Error>> {
+        let package = Package::build(
+            Identifier::Command,
+            Instruction::SetPwd,
+            Payload::SetPwd(match password {
+                Some(password) => password,
+                None => PASSWORD,
+            }),
+        )?;
+
+        self.send(package).await?;
+        self.password = password.unwrap_or(PASSWORD);
+        Ok(())
+    }
+
+    /// Set Module address - SetAdder
+    /// Description: Set Module address.
+    /// Input Parameter: Addr
+    /// Return Parameter: Confirmation code (1 byte)
+    ///     0x00: Address setting complete;
+    ///     0x01: Error when receiving package;
+    ///     0x18: Error when writing FLASH;
     /// Instruction code: 0x15
-    pub fn set_adder(&mut self, address: Address) -> Package {
+    /// Note: The module answers from the new address once it took it, so the driver switches to it on success.
+    pub async fn set_adder(&mut self, address: Address) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::SetAdder,
             Payload::SetAdder(address),
-        );
+        )?;
 
-        package
+        self.transmit(package).await?;
+        self.parser.accept_next_address(address);
+        let acknowledge = self.receive_success().await;
+        if acknowledge.is_ok() {
+            self.address = address;
+        }
+        self.parser = Parser::new(self.address);
+        self.finish(acknowledge)?;
+        Ok(())
     }
 
     /// Set module system's basic parameter - SetSysPara
@@ -938,14 +1802,19 @@ where
     ///     0x18: Error when writing FLASH;
     ///     0x1A: Wrong register number;
     /// Instruction code: 0x0E
-    pub fn set_sys_para(&mut self, parameter: ParameterSetting, content: u8) -> Package {
+    pub async fn set_sys_para(
+        &mut self,
+        parameter: ParameterSetting,
+        content: u8,
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::SetSysPara,
             Payload::SetSysPara(parameter, content),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// Read system Parameter - ReadSysPara
@@ -956,14 +1825,18 @@ where
     ///     0x01: Error when receiving package;
     ///     0x18: Error when writing FLASH;
     /// Instuction code: 0x0F
-    pub fn read_sys_para(&mut self) -> Package {
+    pub async fn read_sys_para(&mut self) -> Result<BasicParameters, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::ReadSysPara,
             Payload::ReadSysPara,
-        );
+        )?;
 
-        package
+        let parameters: BasicParameters = self.request(package).await?;
+        self.stored_packet_length = PacketLength::try_from(parameters.data_packet_size).ok();
+        self.library_size = parameters.finger_libary_size;
+
+        Ok(parameters)
     }
 
     /// Read valid template number - TempleteNum
@@ -973,14 +1846,14 @@ where
     ///     0x00: Read success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x1D
-    pub fn templete_num(&mut self) -> Package {
+    pub async fn templete_num(&mut self) -> Result<u16, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::TempleteNum,
             Payload::TempleteNum,
-        );
+        )?;
 
-        package
+        self.request(package).await
     }
 
     /// Read fingerprint template index table - ReadIndexTable(0x1F)
@@ -990,14 +1863,17 @@ where
     ///     0x00: Read complete;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x1F
-    pub fn read_index_table(&mut self, index_page: IndexPage) -> Package {
+    pub async fn read_index_table(
+        &mut self,
+        index_page: IndexPage,
+    ) -> Result<IndexTable, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::ReadIndexTable,
             Payload::ReadIndexTable(index_page),
-        );
+        )?;
 
-        package
+        self.request(package).await
     }
 
     // # Fingerprint-processing instructions
@@ -1014,10 +1890,11 @@ where
     ///     0x02: Can't detect finger;
     ///     0x03: Fail to collect finger;
     /// Instuction code: 0x01
-    pub fn gen_img(&mut self) -> Package {
-        let package = Package::build(Identifier::Command, Instruction::GenImg, Payload::GenImg);
+    pub async fn gen_img(&mut self) -> Result<(), Error<UART::Error>> {
+        let package = Package::build(Identifier::Command, Instruction::GenImg, Payload::GenImg)?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// I don't know if this function still exists.
@@ -1031,14 +1908,15 @@ where
     ///     0x07: Fail to generate character file due to lackness of character point or over-smallness of fingerprint image;
     ///     0x15: Fail to generate the image for the lackness of valid primary image;
     /// Instuction code: 0x02
-    pub fn img2_tz(&mut self, buffer_id: BufferID) -> Package {
+    pub async fn img2_tz(&mut self, buffer_id: BufferID) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::GenChar,
             Payload::GenChar(buffer_id),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// Upload image - UpImage
@@ -1051,10 +1929,24 @@ where
     /// Instuction code: 0x0A
     /// Note: The upper computer sends the command packet, the module sends the acknowledge packet first, and then sends several data packet.
     /// Note: Packet Bytes N is determined by Packet Length. The value is 128 Bytes before delivery.
-    pub fn up_image(&mut self) -> Package {
-        let package = Package::build(Identifier::Command, Instruction::UpImage, Payload::UpImage);
+    /// Returns the number of bytes written to `image`.
+    pub async fn up_image(&mut self, image: &mut [u8]) -> Result<usize, Error<UART::Error>> {
+        let package = Package::build(Identifier::Command, Instruction::UpImage, Payload::UpImage)?;
 
-        package
+        self.send(package).await?;
+        self.receive_data(image).await
+    }
+
+    /// Uploads the image in the ImageBuffer with UpImage into `image`, which has to get all of it.
+    pub async fn upload_image(
+        &mut self,
+        image: &mut PackedImage,
+    ) -> Result<(), Error<UART::Error>> {
+        let received = self.up_image(&mut image.0).await?;
+        if received != PACKED_IMAGE_SIZE {
+            return Err(Error::IncompleteData);
+        }
+        Ok(())
     }
 
     /// Download the image - DownImage
@@ -1067,14 +1959,35 @@ where
     /// Instuction code: 0x0B
     /// Note: The upper computer sends the command packet, the module sends the acknowledge packet first, and then sends several data packet.
     /// Note: Packet Bytes N is determined by Packet Length. The value is 128 Bytes before delivery.
-    pub fn down_image(&mut self, _image: ImageData) -> Package {
+    /// Note: `image` is sent as is, so it has to already be in the module's transfer format and `PACKED_IMAGE_SIZE` long.
+    pub async fn down_image(&mut self, image: &[u8]) -> Result<(), Error<UART::Error>> {
+        if image.len() != PACKED_IMAGE_SIZE {
+            return Err(Error::InvalidArgument);
+        }
         let package = Package::build(
             Identifier::Command,
             Instruction::DownImage,
             Payload::DownImage,
-        );
+        )?;
+
+        self.send(package).await?;
+        self.send_data(image).await
+    }
+
+    /// Downloads `image` into the ImageBuffer with DownImage.
+    pub async fn download_image(&mut self, image: &PackedImage) -> Result<(), Error<UART::Error>> {
+        self.down_image(image.as_bytes()).await
+    }
 
-        package
+    /// Puts a recorded `image` through the module's feature extractor, as if the sensor had captured it:
+    /// packs it to 4 bits per pixel, downloads it into the ImageBuffer and generates the character file in `buffer_id`.
+    pub async fn replay_image(
+        &mut self,
+        image: &ImageData,
+        buffer_id: BufferID,
+    ) -> Result<(), Error<UART::Error>> {
+        self.download_image(&PackedImage::pack(image)).await?;
+        self.gen_char(buffer_id).await
     }
 
     /// To generate character file from image - GenChar
@@ -1087,14 +2000,15 @@ where
     ///     0x07: Fail to generate character file due to lackness of character point or over-smallness of fingerprint image;
     ///     0x15: Fail to generate the image for the lackness of valid primary image;
     /// Instruction code: 0x02
-    pub fn gen_char(&mut self, buffer_id: BufferID) -> Package {
+    pub async fn gen_char(&mut self, buffer_id: BufferID) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::GenChar,
             Payload::GenChar(buffer_id),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To generate template - RegModel
@@ -1105,14 +2019,15 @@ where
     ///     0x01: Error when receiving package;
     ///     0x0A: Fail to combine the character files. That's, the character files don't belong to one finger.
     /// Instuction code: 0x05
-    pub fn reg_model(&mut self) -> Package {
+    pub async fn reg_model(&mut self) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::RegModel,
             Payload::RegModel,
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To upload character or template - UpChar
@@ -1128,14 +2043,20 @@ where
     /// Note: The upper computer sends the command packet, the module sends the acknowledge packet first, and then sends several data packet.
     /// Note: Packet Bytes N is determined by Packet Length. The value is 128 Bytes before delivery.
     /// Note: The instruction doesn't affect buffer contents.
-    pub fn up_char(&mut self, buffer_id: BufferID) -> Package {
+    /// Returns the number of bytes written to `template`.
+    pub async fn up_char(
+        &mut self,
+        buffer_id: BufferID,
+        template: &mut TemplateData,
+    ) -> Result<usize, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::UpChar,
             Payload::UpChar(buffer_id),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        self.receive_data(template).await
     }
 
     /// Download template - DownChar
@@ -1149,17 +2070,19 @@ where
     /// Note: The upper computer sends the command packet, the module sends the acknowledge packet first, and then sends several data packet.
     /// Note: Packet Bytes N is determined by Packet Length. The value is 128 Bytes before delivery.
     /// Note: The instruction doesn't affect buffer contents.
-    pub fn down_char(&mut self, buffer_id: BufferID, template: CharacterData) -> Package {
+    pub async fn down_char(
+        &mut self,
+        buffer_id: BufferID,
+        template: &TemplateData,
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::DownChar,
             Payload::DownChar(buffer_id),
-        );
+        )?;
 
-        // TODO: This one is going to be a doozy. Going to have to send muliple packets after the first one.
-        let _silence = template;
-
-        package
+        self.send(package).await?;
+        self.send_data(template).await
     }
 
     /// To store template - Store
@@ -1172,17 +2095,19 @@ where
     ///     0x18: Error when writing Flash.
     /// Instuction code: 0x06
     /// Note: CharBufferID is filled with 0x01
-    pub fn store(&mut self, buffer_id: BufferID, model_id: IndexPage) -> Package {
-        // TODO: This one is a little funky. _model_id param expects a [u8; 2].
-        // The first byte being the page number, (0-3)
-        // The second byte being the index in that page. (0-255)
+    pub async fn store(
+        &mut self,
+        buffer_id: BufferID,
+        model_id: ModelId,
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::Store,
             Payload::Store(buffer_id, model_id),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To read template from Flash library - LoadChar
@@ -1195,17 +2120,19 @@ where
     ///     0x0B: Addressing ModelID is beyond the finger library;
     /// Instuction code: 07H
     /// Note: CharBufferID is filled with 0x01
-    pub fn load_char(&mut self, buffer_id: BufferID, model_id: IndexPage) -> Package {
-        // TODO: This one is a little funky. _model_id param expects a [u8; 2].
-        // The first byte being the page number, (0-3)
-        // The second byte being the index in that page. (0-255)
+    pub async fn load_char(
+        &mut self,
+        buffer_id: BufferID,
+        model_id: ModelId,
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::LoadChar,
             Payload::LoadChar(buffer_id, model_id),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To delete template - DeleteChar
@@ -1217,14 +2144,19 @@ where
     ///     0x10: Failed to delete templates;
     ///     0x18: Error when write FLASH;
     /// Instuction code: 0x0C
-    pub fn delete_char(&mut self, start_id: BufferID, num: u8) -> Package {
+    pub async fn delete_char(
+        &mut self,
+        start_id: ModelId,
+        num: u16,
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::DeleteChar,
             Payload::DeleteChar(start_id, num),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To empty finger library - Empty
@@ -1236,10 +2168,11 @@ where
     ///     0x11: Fail to clear finger library;
     ///     0x18: Error when write FLASH;
     /// Instuction code: 0x0D
-    pub fn empty(&mut self) -> Package {
-        let package = Package::build(Identifier::Command, Instruction::Empty, Payload::Empty);
+    pub async fn empty(&mut self) -> Result<(), Error<UART::Error>> {
+        let package = Package::build(Identifier::Command, Instruction::Empty, Payload::Empty)?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To carry out precise matching of two finger templates - Match
@@ -1251,10 +2184,10 @@ where
     ///     0x08: templates of the two buffers aren't matching;
     /// Instuction code: 0x03
     /// Note: The instruction doesn't affect the contents of the buffers.
-    pub fn r#match(&mut self) -> Package {
-        let package = Package::build(Identifier::Command, Instruction::Match, Payload::Match);
+    pub async fn r#match(&mut self) -> Result<MatchScore, Error<UART::Error>> {
+        let package = Package::build(Identifier::Command, Instruction::Match, Payload::Match)?;
 
-        package
+        self.request(package).await
     }
 
     /// To search finger library - Search
@@ -1266,19 +2199,19 @@ where
     ///     0x09: No matching in the library (both the PageID and matching score are 0);
     /// Instuction code: 0x04
     /// Note: The instruction doesn't affect the contents of the buffers.
-    pub fn search(
+    pub async fn search(
         &mut self,
         buffer_id: BufferID,
-        start_page: u16,
+        start_id: ModelId,
         num: u16,
-    ) -> Package {
+    ) -> Result<SearchResult, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::Search,
-            Payload::Search(buffer_id, start_page, num),
-        );
+            Payload::Search(buffer_id, start_id, num),
+        )?;
 
-        package
+        self.request(package).await
     }
 
     /// Fingerprint image collection extension command - GetImageEx(0x28)
@@ -1294,14 +2227,15 @@ where
     ///     0x03: Unsuccessful entry
     ///     0x07: Poor image quality;
     /// Instuction code: 0x28
-    pub fn get_image_ex(&mut self) -> Package {
+    pub async fn get_image_ex(&mut self) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::GetImageEx,
             Payload::GetImageEx,
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// Cancel instruction - Cancel(0x30)
@@ -1311,10 +2245,11 @@ where
     ///     0x00: Cancel setting successful;
     ///     other: Cancel setting failed;
     /// Instuction code: 0x30
-    pub fn cancel(&mut self) -> Package {
-        let package = Package::build(Identifier::Command, Instruction::Cancel, Payload::Cancel);
+    pub async fn cancel(&mut self) -> Result<(), Error<UART::Error>> {
+        let package = Package::build(Identifier::Command, Instruction::Cancel, Payload::Cancel)?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// HandShake - HandShake(0x40)
@@ -1325,14 +2260,15 @@ where
     ///     other: The device is abnormal.
     /// Instuction code: 0x40
     ///     In addition, after the module is powered on, 0x55 will be automatically sent as a handshake sign. After the single-chip microcomputer detects 0x55, it can immediately send commands to enter the working state.
-    pub fn handshake(&mut self) -> Package {
+    pub async fn handshake(&mut self) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::HandShake,
             Payload::HandShake,
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// CheckSensor - CheckSensor (0x36)
@@ -1342,14 +2278,15 @@ where
     ///     0x00: The sensor is normal;
     ///     0x29: the sensor is abnormal;
     /// Instuction code: 0x36
-    pub fn check_sensor(&mut self) -> Package {
+    pub async fn check_sensor(&mut self) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::CheckSensor,
             Payload::CheckSensor,
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// Get the algorithm library version - GetAlgVer (0x39)
@@ -1359,14 +2296,14 @@ where
     ///     0x00: Success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x39
-    pub fn get_alg_ver(&mut self) -> Package {
+    pub async fn get_alg_ver(&mut self) -> Result<AlgVer, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::GetAlgVer,
             Payload::GetAlgVer,
-        );
+        )?;
 
-        package
+        self.request(package).await
     }
 
     /// Get the firmware version - GetFwVer (0x3A)
@@ -1376,14 +2313,14 @@ where
     ///     0x00: Success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x3A
-    pub fn get_fw_ver(&mut self) -> Package {
+    pub async fn get_fw_ver(&mut self) -> Result<FwVer, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::GetFwVer,
             Payload::GetFwVer,
-        );
+        )?;
 
-        package
+        self.request(package).await
     }
 
     /// Read product information - ReadProdInfo (0x3C)
@@ -1393,14 +2330,14 @@ where
     ///     0x00: Success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x3C
-    pub fn read_prod_info(&mut self) -> Package {
+    pub async fn read_prod_info(&mut self) -> Result<ProdInfo, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::ReadProdInfo,
             Payload::ReadProdInfo,
-        );
+        )?;
 
-        package
+        self.request(package).await
     }
 
     /// Soft reset SoftRst (0x3D)
@@ -1411,10 +2348,12 @@ where
     ///     other: Device is abnormal
     /// Instuction code: 0x3D
     ///     After module reset, 0x55 will be automatically sent as a handshake sign. After the single-chip microcomputer detects 0x55, it can immediately send commands to enter the working state.
-    pub fn soft_rst(&mut self) -> Package {
-        let package = Package::build(Identifier::Command, Instruction::SoftRst, Payload::SoftRst);
+    pub async fn soft_rst(&mut self) -> Result<(), Error<UART::Error>> {
+        let package = Package::build(Identifier::Command, Instruction::SoftRst, Payload::SoftRst)?;
 
-        package
+        self.send(package).await?;
+        self.adopt_packet_length();
+        Ok(())
     }
 
     /// Aura control - AuraLedConfig (0x35)
@@ -1424,20 +2363,21 @@ where
     ///     0x00: Success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x35
-    pub fn aura_led_config(
+    pub async fn aura_led_config(
         &mut self,
         ctrl: LightPattern,
         speed: Speed,
         color: Color,
         count: Times,
-    ) -> Package {
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::AuraLedConfig,
             Payload::AuraLedConfig(ctrl, speed, color, count),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// Automatic registration template - AutoEnroll (0x31)
@@ -1463,14 +2403,14 @@ where
     ///     0x26: Times out;
     ///     0x27: Fingerprint already exists;
     /// Note: Model ID: Location ID : 0-0xC7, 0xC8-0xFF is automatic filling (The ID number is assigned by the system; The system will be starting from template 0 to searches the empty templates.)
-    pub fn auto_enroll(
+    pub async fn auto_enroll(
         &mut self,
-        model_id: IndexPage,
+        model_id: u8,
         allow_cover_id: bool,
         allow_duplicate: bool,
         return_in_critical: bool,
         ask_finger_to_leave: bool,
-    ) -> Package {
+    ) -> Result<AutoEnrollStatus, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::AutoEnroll,
@@ -1481,9 +2421,46 @@ where
                 return_in_critical,
                 ask_finger_to_leave,
             ),
-        );
+        )?;
+
+        self.transmit(package).await?;
+        let result = loop {
+            match self.receive_parameters::<AutoEnrollStatus>().await {
+                Ok(status) if return_in_critical && status.step != Paramater1::StoreTemplates => {
+                    continue
+                }
+                result => break result,
+            }
+        };
+        self.finish(result)
+    }
+
+    /// AutoEnroll, returning the status in the critical steps as they happen.
+    /// See `auto_enroll` for the parameters.
+    pub async fn auto_enroll_steps(
+        &mut self,
+        model_id: u8,
+        allow_cover_id: bool,
+        allow_duplicate: bool,
+        ask_finger_to_leave: bool,
+    ) -> Result<AutoEnrollSteps<'_, UART, DELAY>, Error<UART::Error>> {
+        let package = Package::build(
+            Identifier::Command,
+            Instruction::AutoEnroll,
+            Payload::AutoEnroll(
+                model_id,
+                allow_cover_id,
+                allow_duplicate,
+                true,
+                ask_finger_to_leave,
+            ),
+        )?;
 
-        package
+        self.transmit(package).await?;
+        Ok(AutoEnrollSteps {
+            driver: self,
+            done: false,
+        })
     }
 
     /// Automatic fingerprint verification - AutoIdentify (0x32)
@@ -1504,21 +2481,50 @@ where
     ///     0x24: Fingerprint library is empty;
     ///     0x26: Times out;
     /// Instruction code: 0x32
-    pub fn auto_identify(
+    pub async fn auto_identify(
         &mut self,
         grade: SafeGrade,
         start: u8,
         num: u8,
         times: u8,
         return_in_critical: bool,
-    ) -> Package {
+    ) -> Result<AutoIdentifyStatus, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::AutoIdentify,
             Payload::AutoIdentify(grade, start, num, times, return_in_critical),
-        );
+        )?;
+
+        self.transmit(package).await?;
+        let result = loop {
+            match self.receive_parameters::<AutoIdentifyStatus>().await {
+                Ok(status) if return_in_critical && status.step != IdentifyStep::Search => continue,
+                result => break result,
+            }
+        };
+        self.finish(result)
+    }
 
-        package
+    /// AutoIdentify, returning the status in key steps as they happen.
+    /// See `auto_identify` for the parameters.
+    pub async fn auto_identify_steps(
+        &mut self,
+        grade: SafeGrade,
+        start: u8,
+        num: u8,
+        times: u8,
+    ) -> Result<AutoIdentifySteps<'_, UART, DELAY>, Error<UART::Error>> {
+        let package = Package::build(
+            Identifier::Command,
+            Instruction::AutoIdentify,
+            Payload::AutoIdentify(grade, start, num, times, true),
+        )?;
+
+        self.transmit(package).await?;
+        Ok(AutoIdentifySteps {
+            driver: self,
+            done: false,
+        })
     }
 
     // # Other instructions
@@ -1530,14 +2536,14 @@ where
     ///     0x00: Generation success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x14
-    pub fn get_random_code(&mut self) -> Package {
+    pub async fn get_random_code(&mut self) -> Result<u32, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::GetRandomCode,
             Payload::GetRandomCode,
-        );
+        )?;
 
-        package
+        self.request(package).await
     }
 
     /// To read information page - ReadInfPage
@@ -1551,14 +2557,16 @@ where
     /// Note: Module shall transfer following data packet after responding to the upper computer;
     /// Note: Packet Bytes N is determined by Packet Length. The value is 128 Bytes before delivery;
     /// Note: The instruction doesn't affect buffer contents;
-    pub fn read_inf_page(&mut self) -> Package {
+    /// Returns the number of bytes written to `page`.
+    pub async fn read_inf_page(&mut self, page: &mut InfPage) -> Result<usize, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::ReadInfPage,
             Payload::ReadInfPage,
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        self.receive_data(page).await
     }
 
     /// To write note pad - WriteNotepad
@@ -1569,18 +2577,19 @@ where
     ///     0x01: Error when receiving package;
     ///     0x18: Error when write FLASH;
     /// Instuction code: 0x18
-    pub fn write_notepad(
+    pub async fn write_notepad(
         &mut self,
         note_page_number: NotePageNum,
         content: Page,
-    ) -> Package {
+    ) -> Result<(), Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::WriteNotepad,
             Payload::WriteNotepad(note_page_number, content),
-        );
+        )?;
 
-        package
+        self.send(package).await?;
+        Ok(())
     }
 
     /// To read note pad - ReadNotepad
@@ -1590,14 +2599,561 @@ where
     ///     0x00: Read success;
     ///     0x01: Error when receiving package;
     /// Instuction code: 0x19
-    pub fn read_notepad(&mut self, note_page_number: NotePageNum) -> Package {
+    pub async fn read_notepad(
+        &mut self,
+        note_page_number: NotePageNum,
+    ) -> Result<Page, Error<UART::Error>> {
         let package = Package::build(
             Identifier::Command,
             Instruction::ReadNotepad,
             Payload::ReadNotepad(note_page_number),
-        );
+        )?;
 
-        package
+        self.request(package).await
+    }
+}
+
+// # Enrollment
+// Enrolling a finger takes several images of it: each one is turned into a character file in its own CharBuffer, then the character files are merged into a template with RegModel and the template is stored in the library. The finger has to be lifted between images.
+
+/// `enroll` merges at least this many samples.
+pub const ENROLL_SAMPLES_MIN: u8 = 2;
+/// `enroll` merges at most this many samples, one per CharBuffer. The manual recommends at least four.
+pub const ENROLL_SAMPLES_MAX: u8 = 6;
+/// How long `enroll`, `identify` and `verify` pause between GenImg polls while waiting for the finger, in ms.
+const FINGER_POLL: u32 = 50;
+/// How many times `enroll` takes a sample again when the image is too poor for a character file.
+const SAMPLE_RETRIES: usize = 3;
+
+const CHAR_BUFFERS: [BufferID; 6] = [
+    BufferID::CharBuffer1,
+    BufferID::CharBuffer2,
+    BufferID::CharBuffer3,
+    BufferID::CharBuffer4,
+    BufferID::CharBuffer5,
+    BufferID::CharBuffer6,
+];
+
+/// Where `enroll` is at. Samples count from 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnrollProgress {
+    /// Waiting for the finger to be placed for a sample.
+    PlaceFinger(u8),
+    /// The sample is captured and turned into a character file.
+    Captured(u8),
+    /// The sample has to be taken again: the image was too poor for a character file.
+    Retry(u8, ConfirmationCode),
+    /// Waiting for the finger to be lifted after a sample.
+    LiftFinger(u8),
+    /// Merging the samples into a template.
+    Merging,
+    /// The template is stored in the library.
+    Stored(ModelId),
+}
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Enroll a finger at `model_id` from `samples` images of it, `ENROLL_SAMPLES_MIN` to `ENROLL_SAMPLES_MAX`.
+    /// Waits for the finger to be placed and lifted for every sample, and takes a sample again if the image is too poor.
+    /// The Aura LED shows what's going on, the same way AutoEnroll does: blue flashing while waiting for the finger, yellow once the image is taken, white flashing while waiting for the finger to be lifted, then green when the template is stored, or red when enrolling failed.
+    /// Gives up with `Error::Timeout` when the finger isn't placed or lifted within about `timeout` ms.
+    /// That takes a delay to pause between GenImg polls: without one, `timeout` is no time at all, but `timeout / 50 + 1` polls back to back.
+    /// `progress` is called at every step.
+    pub async fn enroll(
+        &mut self,
+        model_id: ModelId,
+        samples: u8,
+        timeout: u32,
+        mut progress: impl FnMut(EnrollProgress),
+    ) -> Result<(), Error<UART::Error>> {
+        if !(ENROLL_SAMPLES_MIN..=ENROLL_SAMPLES_MAX).contains(&samples) {
+            return Err(Error::InvalidArgument);
+        }
+
+        let result = self
+            .enroll_samples(model_id, samples, timeout, &mut progress)
+            .await;
+        let color = match result {
+            Ok(()) => Color::Green,
+            Err(_) => Color::Red,
+        };
+        let led = self
+            .aura_led_config(LightPattern::AlwaysOn, 0, color, 0)
+            .await;
+        result.and(led)
+    }
+
+    async fn enroll_samples(
+        &mut self,
+        model_id: ModelId,
+        samples: u8,
+        timeout: u32,
+        progress: &mut impl FnMut(EnrollProgress),
+    ) -> Result<(), Error<UART::Error>> {
+        for (sample, buffer_id) in (1..=samples).zip(CHAR_BUFFERS) {
+            let mut retries = 0;
+            loop {
+                progress(EnrollProgress::PlaceFinger(sample));
+                self.aura_led_config(LightPattern::Flashing, 0x40, Color::Blue, 0)
+                    .await?;
+                self.wait_for_finger(true, timeout).await?;
+                self.aura_led_config(LightPattern::AlwaysOn, 0, Color::Yellow, 0)
+                    .await?;
+
+                let retry = match self.gen_char(buffer_id).await {
+                    Ok(()) => false,
+                    Err(Error::Confirmation(code))
+                        if poor_image(code) && retries < SAMPLE_RETRIES =>
+                    {
+                        retries += 1;
+                        progress(EnrollProgress::Retry(sample, code));
+                        true
+                    }
+                    Err(error) => return Err(error),
+                };
+                if !retry {
+                    progress(EnrollProgress::Captured(sample));
+                }
+
+                if retry || sample < samples {
+                    progress(EnrollProgress::LiftFinger(sample));
+                    self.aura_led_config(LightPattern::Flashing, 0x40, Color::White, 0)
+                        .await?;
+                    self.wait_for_finger(false, timeout).await?;
+                }
+                if !retry {
+                    break;
+                }
+            }
+        }
+
+        progress(EnrollProgress::Merging);
+        self.reg_model().await?;
+        self.store(BufferID::CharBuffer1, model_id).await?;
+        progress(EnrollProgress::Stored(model_id));
+        Ok(())
+    }
+
+    /// Polls GenImg until the finger is on the sensor, or off it, for about `timeout` ms, pausing `FINGER_POLL` ms between polls.
+    /// Without a delay, polls `timeout / FINGER_POLL + 1` times without pausing. On success with `placed`, the ImageBuffer holds the image.
+    async fn wait_for_finger(
+        &mut self,
+        placed: bool,
+        timeout: u32,
+    ) -> Result<(), Error<UART::Error>> {
+        for _ in 0..=timeout / FINGER_POLL {
+            match self.gen_img().await {
+                Ok(()) if placed => return Ok(()),
+                Err(Error::Confirmation(ConfirmationCode::NoFingerOnSensor)) if !placed => {
+                    return Ok(())
+                }
+                Ok(())
+                | Err(Error::Confirmation(ConfirmationCode::NoFingerOnSensor))
+                | Err(Error::Confirmation(ConfirmationCode::FailToEnrollFinger)) => {}
+                Err(error) => return Err(error),
+            }
+            if let Some(delay) = &mut self.delay {
+                delay.delay_ms(FINGER_POLL).await;
+            }
+        }
+        Err(Error::Timeout)
+    }
+}
+
+// # Identification
+// Searching the whole library for a finger (1:N) and checking a finger against one template (1:1). Not finding a match is an answer, not an error: both routines return `None` for it.
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Captures the finger on the sensor, waiting for it for about `timeout` ms, and searches the whole library for it.
+    /// Without a delay, `timeout` counts GenImg polls rather than time, like in `enroll`.
+    /// Returns the matching template, or `None` when there is none or it scores below `min_score`.
+    /// The module only reports matches passing its `SecurityLevel`; `min_score` can only be stricter.
+    pub async fn identify(
+        &mut self,
+        min_score: MatchScore,
+        timeout: u32,
+    ) -> Result<Option<SearchResult>, Error<UART::Error>> {
+        self.capture(BufferID::CharBuffer1, timeout).await?;
+
+        let start = ModelId::default();
+        let result = self
+            .search(BufferID::CharBuffer1, start, self.library_size)
+            .await;
+        match result {
+            Ok(result) => Ok(Some(result).filter(|result| result.score >= min_score)),
+            Err(Error::Confirmation(ConfirmationCode::FailToFindMatchingFinger)) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Captures the finger on the sensor, waiting for it for about `timeout` ms, and matches it against the template at `model_id`.
+    /// Without a delay, `timeout` counts GenImg polls rather than time, like in `enroll`.
+    /// Returns the score, or `None` when the finger doesn't match or scores below `min_score`.
+    pub async fn verify(
+        &mut self,
+        model_id: ModelId,
+        min_score: MatchScore,
+        timeout: u32,
+    ) -> Result<Option<MatchScore>, Error<UART::Error>> {
+        self.capture(BufferID::CharBuffer1, timeout).await?;
+        self.load_char(BufferID::CharBuffer2, model_id).await?;
+
+        match self.r#match().await {
+            Ok(score) => Ok(Some(score).filter(|score| *score >= min_score)),
+            Err(Error::Confirmation(ConfirmationCode::FailFingerDoesntMatch)) => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Waits for the finger and turns its image into a character file in `buffer_id`.
+    async fn capture(
+        &mut self,
+        buffer_id: BufferID,
+        timeout: u32,
+    ) -> Result<(), Error<UART::Error>> {
+        self.wait_for_finger(true, timeout).await?;
+        self.gen_char(buffer_id).await
+    }
+}
+
+/// The module couldn't make a character file from the image, but another image of the finger may do.
+fn poor_image(code: ConfirmationCode) -> bool {
+    matches!(
+        code,
+        ConfirmationCode::FailToGenerateCharacterOverDisorderlyFingerprintImage
+            | ConfirmationCode::FailToGenerateCharacterLacknessOfCharacterPointOrOverSmallness
+            | ConfirmationCode::FailToGenerateImageLacknessOfValidPrimaryImage
+    )
+}
+
+// # System parameters
+// Baud rate, security level and data packet length are written to flash with SetSysPara, but the module only works with new values after it is powered on again. The setters below write a value only when it differs, read it back, and optionally restart the module with SoftRst to put it into effect.
+
+/// How long the setters wait for the module to be ready after SoftRst, in ms.
+const RESTART_TIMEOUT: u32 = 500;
+/// How long `wait_until_idle` pauses between polls, in ms.
+const IDLE_POLL: u32 = 10;
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Sets the module's baud rate. The module goes on talking at the previous one until it is powered on again,
+    /// see `set_baud_rate_with_reset` to switch right away.
+    pub async fn set_baud_rate(&mut self, baud_rate: BaudRate) -> Result<(), Error<UART::Error>> {
+        self.update_sys_para(ParameterSetting::BaudRateControl, baud_rate as u8)
+            .await
+    }
+
+    /// Sets the module's baud rate and restarts the module, which then talks at `baud_rate`:
+    /// sets the UART up for it with `configure`, then brings the module back like `init`. `baud_rate()` follows.
+    pub async fn set_baud_rate_with_reset(
+        &mut self,
+        baud_rate: BaudRate,
+        configure: impl FnOnce(&mut UART, BaudRate),
+    ) -> Result<(), Error<UART::Error>> {
+        self.set_baud_rate(baud_rate).await?;
+        self.soft_rst().await?;
+        configure(&mut self.uart, baud_rate);
+        self.baud_rate = baud_rate;
+        self.init(RESTART_TIMEOUT).await?;
+        Ok(())
+    }
+
+    /// Finds the baud rate the module talks at, when it isn't known: sets the UART up for every `BaudRate` in turn with `configure`,
+    /// starting with `baud_rate()`, until the module answers HandShake within `timeout` ms. A module talking at another rate never answers,
+    /// so the driver needs a delay: without one, returns `Error::InvalidArgument`. Then verifies the password like `init` does, and returns the baud rate and the system parameters.
+    /// Returns `None`, with the UART set up for `baud_rate()` again, if the module doesn't answer at any rate.
+    pub async fn autobaud(
+        &mut self,
+        timeout: u32,
+        mut configure: impl FnMut(&mut UART, BaudRate),
+    ) -> Result<Option<(BaudRate, BasicParameters)>, Error<UART::Error>> {
+        if self.delay.is_none() {
+            return Err(Error::InvalidArgument);
+        }
+        let current = self.baud_rate;
+        let candidates = BaudRate::ALL.into_iter().filter(|&rate| rate != current);
+        for baud_rate in core::iter::once(current).chain(candidates) {
+            configure(&mut self.uart, baud_rate);
+            // Nothing sent at another rate can be answered at this one.
+            self.parser.reset();
+            self.pending = false;
+            match self.with_timeout(timeout, Self::handshake).await {
+                // Any acknowledge packet gets through the checksum only at the right rate.
+                Ok(()) | Err(Error::Confirmation(_)) => {
+                    self.baud_rate = baud_rate;
+                    let parameters = self.log_in(timeout).await?;
+                    return Ok(Some((baud_rate, parameters)));
+                }
+                Err(
+                    Error::Timeout
+                    | Error::UnexpectedEof
+                    | Error::Frame(_)
+                    | Error::UnexpectedIdentifier(_),
+                ) => continue,
+                Err(error) => {
+                    configure(&mut self.uart, current);
+                    return Err(error);
+                }
+            }
+        }
+
+        configure(&mut self.uart, current);
+        Ok(None)
+    }
+
+    /// Sets the security level searching and matching use. With `reset`, restarts the module to put it into effect.
+    pub async fn set_security_level(
+        &mut self,
+        security_level: SecurityLevel,
+        reset: bool,
+    ) -> Result<(), Error<UART::Error>> {
+        self.update_sys_para(ParameterSetting::SecurityLevel, security_level as u8)
+            .await?;
+        if reset {
+            self.restart().await?;
+        }
+        Ok(())
+    }
+
+    /// Sets the data packet length. With `reset`, restarts the module, and the driver splits data into packets of the new length;
+    /// without, both go on with the length in effect until the module is powered on again.
+    pub async fn set_packet_length(
+        &mut self,
+        packet_length: PacketLength,
+        reset: bool,
+    ) -> Result<(), Error<UART::Error>> {
+        self.update_sys_para(ParameterSetting::DataPackageLength, packet_length as u8)
+            .await?;
+        if reset {
+            self.restart().await?;
+        }
+        Ok(())
+    }
+
+    /// Polls the system status register with ReadSysPara until the module isn't busy, for about `timeout` ms,
+    /// pausing between polls if the driver has a delay. Returns the register, or `Error::Timeout` if the module stays busy.
+    pub async fn wait_until_idle(
+        &mut self,
+        timeout: u32,
+    ) -> Result<SystemRegister, Error<UART::Error>> {
+        for _ in 0..=timeout / IDLE_POLL {
+            let register = self.read_sys_para().await?.status_register;
+            if !register.is_busy() {
+                return Ok(register);
+            }
+            if let Some(delay) = &mut self.delay {
+                delay.delay_ms(IDLE_POLL).await;
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Writes `value` unless the module has it already, and checks it reads back.
+    async fn update_sys_para(
+        &mut self,
+        parameter: ParameterSetting,
+        value: u8,
+    ) -> Result<(), Error<UART::Error>> {
+        if self.read_sys_para().await?.setting(parameter) == value as u16 {
+            return Ok(());
+        }
+
+        self.set_sys_para(parameter, value).await?;
+        if self.read_sys_para().await?.setting(parameter) != value as u16 {
+            return Err(Error::ReadBack);
+        }
+        Ok(())
+    }
+
+    /// Restarts the module with SoftRst and brings it back with `init`, which also puts the new packet length into effect.
+    async fn restart(&mut self) -> Result<(), Error<UART::Error>> {
+        self.soft_rst().await?;
+        self.init(RESTART_TIMEOUT).await?;
+        Ok(())
+    }
+}
+
+// # Fingerprint library
+// ReadIndexTable tells which locations hold a template, 256 locations per index page.
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Reads the index pages covering the library, see `library_size`, and checks the number of templates against TempleteNum.
+    pub async fn read_library_index(&mut self) -> Result<LibraryIndex, Error<UART::Error>> {
+        let mut tables = [[0; 32]; 4];
+        let pages = (self.library_size as usize).div_ceil(256);
+        for (table, page) in tables.iter_mut().zip(IndexPage::ALL).take(pages) {
+            *table = self.read_index_table(page).await?;
+        }
+
+        let index = LibraryIndex::new(tables, self.library_size);
+        if index.count() != self.templete_num().await? {
+            return Err(Error::IndexMismatch);
+        }
+        Ok(index)
+    }
+}
+
+// # Provisioning
+// The password and the address are written to flash, and a module nobody knows the credentials of any more can't be talked to. `provision` changes them and confirms the module answers to the new ones before it returns, putting the old ones back otherwise; `probe` finds a module's credentials among known ones.
+
+/// What a module answers to: the address packages are sent to, and the handshake password.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Credentials {
+    pub address: Address,
+    pub password: Password,
+}
+
+impl Default for Credentials {
+    /// Factory settings.
+    fn default() -> Self {
+        Self {
+            address: ADDRESS,
+            password: PASSWORD,
+        }
+    }
+}
+
+impl<UART, DELAY> Driver<UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Credentials the driver talks to the module with.
+    pub fn credentials(&self) -> Credentials {
+        Credentials {
+            address: self.address,
+            password: self.password,
+        }
+    }
+
+    /// Changes the module's address and password to `credentials`, then verifies the password at the new address.
+    /// If anything goes wrong on the way, finds out what the module answers to now and sets the previous credentials again,
+    /// before returning the error. Returns `Error::CredentialsUnknown` instead if that fails too. Waits up to `timeout` ms for each answer; a module at another address never answers, so the driver needs a delay.
+    /// Without one, returns `Error::InvalidArgument`. The module has to be verified already, see `init`.
+    pub async fn provision(
+        &mut self,
+        credentials: Credentials,
+        timeout: u32,
+    ) -> Result<(), Error<UART::Error>> {
+        if self.delay.is_none() {
+            return Err(Error::InvalidArgument);
+        }
+        let previous = self.credentials();
+        let result = self.change_credentials(credentials, timeout).await;
+        if result.is_err() {
+            // Either change may have been written, or not.
+            let candidates = [
+                previous,
+                credentials,
+                Credentials {
+                    address: previous.address,
+                    password: credentials.password,
+                },
+                Credentials {
+                    address: credentials.address,
+                    password: previous.password,
+                },
+            ];
+            let rollback = match self.probe(&candidates, timeout).await {
+                Ok(Some(_)) => self.change_credentials(previous, timeout).await,
+                Ok(None) => Err(Error::Timeout),
+                Err(error) => Err(error),
+            };
+            if rollback.is_err() {
+                return Err(Error::CredentialsUnknown);
+            }
+        }
+
+        result
+    }
+
+    /// Tries `candidates` in order, and returns the first credentials the module answers to.
+    /// The driver goes on with them, or keeps its own if there is no match. Waits up to `timeout` ms for each answer,
+    /// which needs a delay, as a module at another address never answers. Without one, returns `Error::InvalidArgument`.
+    pub async fn probe(
+        &mut self,
+        candidates: &[Credentials],
+        timeout: u32,
+    ) -> Result<Option<Credentials>, Error<UART::Error>> {
+        if self.delay.is_none() {
+            return Err(Error::InvalidArgument);
+        }
+        let own = self.credentials();
+        for &candidate in candidates {
+            match self.connect(candidate, timeout).await {
+                Ok(()) => return Ok(Some(candidate)),
+                Err(
+                    Error::Timeout
+                    | Error::UnexpectedEof
+                    | Error::Frame(_)
+                    | Error::Confirmation(ConfirmationCode::WrongPassword),
+                ) => continue,
+                Err(error) => {
+                    self.use_credentials(own);
+                    return Err(error);
+                }
+            }
+        }
+
+        self.use_credentials(own);
+        Ok(None)
+    }
+
+    /// Writes whichever of address and password differ from the driver's, then connects with `credentials`.
+    async fn change_credentials(
+        &mut self,
+        credentials: Credentials,
+        timeout: u32,
+    ) -> Result<(), Error<UART::Error>> {
+        self.with_timeout(timeout, async |driver| {
+            // A new password has to be verified before the module takes further commands.
+            if credentials.address != driver.address {
+                driver.set_adder(credentials.address).await?;
+            }
+            if credentials.password != driver.password {
+                driver.set_pwd(Some(credentials.password)).await?;
+            }
+            Ok(())
+        })
+        .await?;
+
+        self.connect(credentials, timeout).await
+    }
+
+    /// Switches the driver to `credentials` and verifies the password with them, as after power-on.
+    async fn connect(
+        &mut self,
+        credentials: Credentials,
+        timeout: u32,
+    ) -> Result<(), Error<UART::Error>> {
+        self.use_credentials(credentials);
+        self.with_timeout(timeout, async |driver| {
+            driver.vfy_pwd(Some(credentials.password)).await
+        })
+        .await
+    }
+
+    /// Talks to the module with `credentials` from now on. An answer still on its way was meant for the old ones,
+    /// so there is nothing to recover: a module that doesn't take the new credentials answers nothing the driver could get wrong.
+    fn use_credentials(&mut self, credentials: Credentials) {
+        self.address = credentials.address;
+        self.parser = Parser::new(credentials.address);
+        self.password = credentials.password;
+        self.pending = false;
     }
 }
 
@@ -1620,23 +3176,95 @@ pub fn get_u32_as_u16_parts(value: u32) -> [u16; 4] {
 
 // # Module Defined Types
 
-#[derive(Default)]
-#[allow(dead_code)]
+/// Return parameters of an instruction, decoded from its acknowledge packet.
+trait Response: Sized {
+    /// `None` when there aren't enough bytes.
+    fn decode(parameters: &[u8]) -> Option<Self>;
+}
+
+/// Reads a number stored high byte first at `at`.
+fn be_bytes<const N: usize>(parameters: &[u8], at: usize) -> Option<[u8; N]> {
+    parameters.get(at..at + N)?.try_into().ok()
+}
+
+/// Reads an ASCII string padded with 0x00 at `at`.
+fn ascii<const N: usize>(parameters: &[u8], at: usize) -> Option<[char; N]> {
+    let bytes: [u8; N] = be_bytes(parameters, at)?;
+    Some(bytes.map(char::from))
+}
+
+impl Response for u16 {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(u16::from_be_bytes(be_bytes(parameters, 0)?))
+    }
+}
+
+impl Response for u32 {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(u32::from_be_bytes(be_bytes(parameters, 0)?))
+    }
+}
+
+impl<const N: usize> Response for [u8; N] {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        be_bytes(parameters, 0)
+    }
+}
+
+impl<const N: usize> Response for String<N> {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        let bytes = parameters.get(..N)?;
+        let end = bytes.iter().position(|byte| *byte == 0x00).unwrap_or(N);
+
+        let mut string = String::new();
+        string
+            .push_str(core::str::from_utf8(&bytes[..end]).ok()?)
+            .ok()?;
+        Some(string)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct BasicParameters {
     /// Contents of system status register
-    status_register: SystemRegister,
+    pub status_register: SystemRegister,
     /// Fixed value: 0x0009
-    system_identifier_code: u16,
+    pub system_identifier_code: u16,
     /// Finger library size
-    finger_libary_size: u16,
+    pub finger_libary_size: u16,
     /// Security level (1, 2, 3, 4, 5)
-    security_level: u16,
+    pub security_level: u16,
     /// 32-bit device address
-    device_address: [u8; 4],
+    pub device_address: [u8; 4],
     /// Size code (0, 1, 2, 3)
-    data_packet_size: u16,
+    pub data_packet_size: u16,
     /// N (baud = 9600*N bps)
-    buad_setting: u16,
+    pub buad_setting: u16,
+}
+
+impl BasicParameters {
+    /// The value of a parameter SetSysPara writes.
+    pub fn setting(&self, parameter: ParameterSetting) -> u16 {
+        match parameter {
+            ParameterSetting::BaudRateControl => self.buad_setting,
+            ParameterSetting::SecurityLevel => self.security_level,
+            ParameterSetting::DataPackageLength => self.data_packet_size,
+        }
+    }
+}
+
+impl Response for BasicParameters {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(Self {
+            status_register: SystemRegister::from(u16::from_be_bytes(be_bytes(parameters, 0)?)),
+            system_identifier_code: u16::from_be_bytes(be_bytes(parameters, 2)?),
+            finger_libary_size: u16::from_be_bytes(be_bytes(parameters, 4)?),
+            security_level: u16::from_be_bytes(be_bytes(parameters, 6)?),
+            device_address: be_bytes(parameters, 8)?,
+            data_packet_size: u16::from_be_bytes(be_bytes(parameters, 12)?),
+            buad_setting: u16::from_be_bytes(be_bytes(parameters, 14)?),
+        })
+    }
 }
 
 /// Index tables are read per page, 256 templates per page
@@ -1652,11 +3280,69 @@ pub enum IndexPage {
     Page3 = 3,
 }
 
+impl IndexPage {
+    /// Every index page, covering ModelIDs 0 ~ 1023.
+    pub const ALL: [IndexPage; 4] = [
+        IndexPage::Page0,
+        IndexPage::Page1,
+        IndexPage::Page2,
+        IndexPage::Page3,
+    ];
+}
+
 /// Index table structure: every 8 bits is a group, and each group is output starting from the high position.
 /// Data "0" in the index table means that there is no valid template in the corresponding position;
 /// Data "1" means that there is a valid template in the corresponding position.
 pub type IndexTable = [u8; 32];
 
+/// Which locations of the fingerprint library hold a template, from its index tables.
+/// Bit `n` (LSB first) of byte `m` of page `p` stands for ModelID `256 * p + 8 * m + n`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LibraryIndex {
+    tables: [IndexTable; 4],
+    library_size: u16,
+}
+
+impl LibraryIndex {
+    /// `tables` holds the index tables of `IndexPage::ALL`, in order. Locations from `library_size` on are left out.
+    pub fn new(tables: [IndexTable; 4], library_size: u16) -> Self {
+        Self {
+            tables,
+            library_size: library_size.min(1024),
+        }
+    }
+
+    pub fn library_size(&self) -> u16 {
+        self.library_size
+    }
+
+    /// Whether a template is stored at `model_id`.
+    pub fn is_occupied(&self, model_id: ModelId) -> bool {
+        let id = model_id.get() as usize;
+        model_id.get() < self.library_size
+            && self.tables[id / 256][id % 256 / 8] & (1 << (id % 8)) != 0
+    }
+
+    /// The locations holding a template, in order.
+    pub fn iter_occupied(&self) -> impl Iterator<Item = ModelId> + '_ {
+        (0..self.library_size)
+            .map(ModelId)
+            .filter(|&model_id| self.is_occupied(model_id))
+    }
+
+    /// The first location without a template, or `None` when the library is full.
+    pub fn first_free(&self) -> Option<ModelId> {
+        (0..self.library_size)
+            .map(ModelId)
+            .find(|&model_id| !self.is_occupied(model_id))
+    }
+
+    /// Number of templates stored.
+    pub fn count(&self) -> u16 {
+        self.iter_occupied().count() as u16
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Copy, Default)]
 pub enum BufferID {
@@ -1669,8 +3355,182 @@ pub enum BufferID {
     CharBuffer6 = 0x06,
 }
 
-#[allow(dead_code)]
-type MatchScore = u16;
+pub type MatchScore = u16;
+
+/// ModelID: the location of a template in the fingerprint library.
+/// The library starts from 0, so valid IDs are `0..finger_libary_size`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModelId(u16);
+
+impl ModelId {
+    /// Returns `None` when `id` is beyond the finger library.
+    /// `library_size` is `BasicParameters.finger_libary_size`, as reported by `read_sys_para`.
+    pub fn new(id: u16, library_size: u16) -> Option<Self> {
+        (id < library_size).then_some(Self(id))
+    }
+
+    pub fn get(self) -> u16 {
+        self.0
+    }
+}
+
+impl From<ModelId> for u16 {
+    fn from(item: ModelId) -> Self {
+        item.0
+    }
+}
+
+/// An image the way UpImage and DownImage transfer it: only the high four bits of every pixel,
+/// two pixels per byte, the first one in the high half.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PackedImage([u8; PACKED_IMAGE_SIZE]);
+
+impl PackedImage {
+    pub fn new(data: [u8; PACKED_IMAGE_SIZE]) -> Self {
+        Self(data)
+    }
+
+    /// Keeps the high four bits of every pixel of `image`.
+    pub fn pack(image: &ImageData) -> Self {
+        let mut packed = Self::default();
+        for (byte, pair) in packed.0.iter_mut().zip(image.chunks_exact(2)) {
+            *byte = (pair[0] & 0xF0) | (pair[1] >> 4);
+        }
+        packed
+    }
+
+    /// Expands the pixels into `image`, spreading the 16 levels over all 256: 0x0 to 0x00, 0xF to 0xFF.
+    pub fn unpack(&self, image: &mut ImageData) {
+        for (pair, byte) in image.chunks_exact_mut(2).zip(self.0) {
+            pair[0] = (byte >> 4) * 0x11;
+            pair[1] = (byte & 0x0F) * 0x11;
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8; PACKED_IMAGE_SIZE] {
+        &self.0
+    }
+}
+
+impl Default for PackedImage {
+    fn default() -> Self {
+        Self([0; PACKED_IMAGE_SIZE])
+    }
+}
+
+impl core::fmt::Debug for PackedImage {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PackedImage").finish_non_exhaustive()
+    }
+}
+
+/// Returned by Search and AutoIdentify: where the matching template is, and how well it matched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchResult {
+    /// ModelID (template number)
+    pub page_id: ModelId,
+    /// MatchScore
+    pub score: MatchScore,
+}
+
+impl Response for SearchResult {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(Self {
+            page_id: ModelId(u16::from_be_bytes(be_bytes(parameters, 0)?)),
+            score: u16::from_be_bytes(be_bytes(parameters, 2)?),
+        })
+    }
+}
+
+/// Returned by AutoEnroll: the step the module reported, and the ModelID the template goes to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutoEnrollStatus {
+    /// Parameter1
+    pub step: Paramater1,
+    /// Parameter2
+    pub model_id: u8,
+}
+
+impl Response for AutoEnrollStatus {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(Self {
+            step: Paramater1::try_from(*parameters.first()?).ok()?,
+            model_id: *parameters.get(1)?,
+        })
+    }
+}
+
+/// Returned by AutoIdentify: the step the module reported, and the match.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AutoIdentifyStatus {
+    /// Parameter
+    pub step: IdentifyStep,
+    /// ModelID + MatchScore
+    pub result: SearchResult,
+}
+
+impl Response for AutoIdentifyStatus {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(Self {
+            step: IdentifyStep::try_from(*parameters.first()?).ok()?,
+            result: SearchResult::decode(parameters.get(1..)?)?,
+        })
+    }
+}
+
+/// An AutoEnroll in progress, see `Driver::auto_enroll_steps`.
+pub struct AutoEnrollSteps<'a, UART, DELAY = NoDelay> {
+    driver: &'a mut Driver<UART, DELAY>,
+    done: bool,
+}
+
+impl<UART, DELAY> AutoEnrollSteps<'_, UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Waits for the module to report the next step.
+    /// Returns `None` after the template is stored, or after the module reported a failure.
+    pub async fn next(&mut self) -> Option<Result<AutoEnrollStatus, Error<UART::Error>>> {
+        if self.done {
+            return None;
+        }
+
+        let status = self.driver.receive_parameters::<AutoEnrollStatus>().await;
+        self.done = !matches!(status, Ok(status) if status.step != Paramater1::StoreTemplates);
+        if self.done {
+            return Some(self.driver.finish(status));
+        }
+        Some(status)
+    }
+}
+
+/// An AutoIdentify in progress, see `Driver::auto_identify_steps`.
+pub struct AutoIdentifySteps<'a, UART, DELAY = NoDelay> {
+    driver: &'a mut Driver<UART, DELAY>,
+    done: bool,
+}
+
+impl<UART, DELAY> AutoIdentifySteps<'_, UART, DELAY>
+where
+    UART: Read + Write,
+    DELAY: DelayNs,
+{
+    /// Waits for the module to report the next step.
+    /// Returns `None` after the search result, or after the module reported a failure.
+    pub async fn next(&mut self) -> Option<Result<AutoIdentifyStatus, Error<UART::Error>>> {
+        if self.done {
+            return None;
+        }
+
+        let status = self.driver.receive_parameters::<AutoIdentifyStatus>().await;
+        self.done = !matches!(status, Ok(status) if status.step != IdentifyStep::Search);
+        if self.done {
+            return Some(self.driver.finish(status));
+        }
+        Some(status)
+    }
+}
 
 #[allow(dead_code)]
 pub struct PageIndex {
@@ -1678,36 +3538,53 @@ pub struct PageIndex {
     entry: u8,
 }
 
-pub type AlgVer = u32;
+/// Algorithm library version string
+pub type AlgVer = String<32>;
 
-pub type FwVer = u32;
+/// Firmware version string
+pub type FwVer = String<32>;
 
 /// Product information: store in the following order. For Numbers, the high byte comes first. For a string, the insufficient part is 0x00.
-#[derive(Default)]
-#[allow(dead_code)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct ProdInfo {
     /// module type, ASCII
-    fpm_model: [char; 16],
+    pub fpm_model: [char; 16],
     /// Module batch number, ASCII
-    bn: [char; 4],
+    pub bn: [char; 4],
     /// Module serial number, ASCII
-    sn: [char; 8],
+    pub sn: [char; 8],
     /// For the hardware version, the first byte represents the main version and the second byte represents the sub-version
-    hw_ver: [u8; 2],
+    pub hw_ver: [u8; 2],
     /// Finger Print Sensor Model, ASCII
-    fps_model: [char; 8],
+    pub fps_model: [char; 8],
     /// Finger Print Sensor Width
-    fps_width: u16,
+    pub fps_width: u16,
     /// Finger Print Sensor Height
-    fps_height: u16,
+    pub fps_height: u16,
     /// Template size
-    tmpl_size: u16,
+    pub tmpl_size: u16,
     /// Fingerprint database size
-    tmpl_total: u16,
+    pub tmpl_total: u16,
+}
+
+impl Response for ProdInfo {
+    fn decode(parameters: &[u8]) -> Option<Self> {
+        Some(Self {
+            fpm_model: ascii(parameters, 0)?,
+            bn: ascii(parameters, 16)?,
+            sn: ascii(parameters, 20)?,
+            hw_ver: be_bytes(parameters, 28)?,
+            fps_model: ascii(parameters, 30)?,
+            fps_width: u16::from_be_bytes(be_bytes(parameters, 38)?),
+            fps_height: u16::from_be_bytes(be_bytes(parameters, 40)?),
+            tmpl_size: u16::from_be_bytes(be_bytes(parameters, 42)?),
+            tmpl_total: u16::from_be_bytes(be_bytes(parameters, 44)?),
+        })
+    }
 }
 
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum LightPattern {
     Breathing = 0x01,
     Flashing = 0x02,
@@ -1723,12 +3600,28 @@ impl From<LightPattern> for u8 {
     }
 }
 
+impl TryFrom<u8> for LightPattern {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Breathing),
+            0x02 => Ok(Self::Flashing),
+            0x03 => Ok(Self::AlwaysOn),
+            0x04 => Ok(Self::AlwaysOff),
+            0x05 => Ok(Self::GraduallyOn),
+            0x06 => Ok(Self::GraduallyOff),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Speed: 0x00-0xff, 256 gears, Minimum 5s cycle.
 /// It is effective for breathing lamp and flashing lamp, Light gradually on, Light gradually off
 pub type Speed = u8;
 
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Color {
     Red = 0b0000_0001,    // 0x01,
     Blue = 0b0000_0010,   // 0x02,
@@ -1745,12 +3638,31 @@ impl From<Color> for u8 {
     }
 }
 
+impl TryFrom<u8> for Color {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Red),
+            0x02 => Ok(Self::Blue),
+            0x03 => Ok(Self::Purple),
+            0x04 => Ok(Self::Green),
+            0x05 => Ok(Self::Yellow),
+            0x06 => Ok(Self::Cyan),
+            0x07 => Ok(Self::White),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Number of cycles: 0 = infinite, 1-255.
 /// It is effective for with breathing light and flashing light.
 type Times = u8;
 
-#[allow(dead_code)]
-enum Paramater1 {
+/// The steps of AutoEnroll, reported when the module is asked to return the status in the critical steps.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Paramater1 {
     /// 0x01: Collect image for the first time
     CollectFirst = 0x01,
     /// 0x02: Generate Feature for the first time
@@ -1769,15 +3681,13 @@ enum Paramater1 {
     FeatureFourth = 0x08,
     /// 0x09: Collect image for the fifth time
     CollectFifth = 0x09,
-    /// 0x10: Generate Feature for the fifth time
-    /// TODO: Double Check this. Migth be 0x0A
-    FeatureFifth = 0x10,
-    /// 0x11: Collect image for the sixth time
-    /// TODO: Double Check this. Migth be 0x0B
-    CollectSixth = 0x11,
-    /// 0x12: Generate Feature for the sixth time
-    /// TODO: Double Check this. Migth be 0x0C
-    FeatureSixth = 0x12,
+    /// 0x0A: Generate Feature for the fifth time
+    /// The manual writes this one as 0x10, the steps are numbered in decimal from here up to the sixth feature.
+    FeatureFifth = 0x0A,
+    /// 0x0B: Collect image for the sixth time
+    CollectSixth = 0x0B,
+    /// 0x0C: Generate Feature for the sixth time
+    FeatureSixth = 0x0C,
     /// 0x0D: Repeat fingerprint check
     FingerCheck = 0x0D,
     /// 0x0E: Merge feature
@@ -1786,6 +3696,71 @@ enum Paramater1 {
     StoreTemplates = 0x0F,
 }
 
+impl Paramater1 {
+    /// The image, 1 to 6, that a collect or generate feature step is about.
+    pub fn sample(self) -> Option<u8> {
+        match self {
+            Self::FingerCheck | Self::MergeFeatures | Self::StoreTemplates => None,
+            step => Some((step as u8).div_ceil(2)),
+        }
+    }
+
+    /// Whether the step collected an image. After it, the finger may be lifted.
+    pub fn is_collect(self) -> bool {
+        self.sample().is_some() && self as u8 % 2 == 1
+    }
+}
+
+impl TryFrom<u8> for Paramater1 {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::CollectFirst),
+            0x02 => Ok(Self::FeatureFirst),
+            0x03 => Ok(Self::CollectSecond),
+            0x04 => Ok(Self::FeatureSecond),
+            0x05 => Ok(Self::CollectThird),
+            0x06 => Ok(Self::FeatureThird),
+            0x07 => Ok(Self::CollectFourth),
+            0x08 => Ok(Self::FeatureFourth),
+            0x09 => Ok(Self::CollectFifth),
+            0x0A => Ok(Self::FeatureFifth),
+            0x0B => Ok(Self::CollectSixth),
+            0x0C => Ok(Self::FeatureSixth),
+            0x0D => Ok(Self::FingerCheck),
+            0x0E => Ok(Self::MergeFeatures),
+            0x0F => Ok(Self::StoreTemplates),
+            _ => Err(value),
+        }
+    }
+}
+
+/// The steps of AutoIdentify, reported when the module is asked to return the status in key steps.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentifyStep {
+    /// 0x01: Collect image
+    Collect = 0x01,
+    /// 0x02: Generate feature
+    Feature = 0x02,
+    /// 0x05: Search result
+    Search = 0x05,
+}
+
+impl TryFrom<u8> for IdentifyStep {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::Collect),
+            0x02 => Ok(Self::Feature),
+            0x05 => Ok(Self::Search),
+            _ => Err(value),
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 pub enum SafeGrade {
     Low = 1,
@@ -1794,12 +3769,16 @@ pub enum SafeGrade {
     HighMid = 4,
     High = 5,
 }
-pub type Page = [u8; 512];
+/// A notepad page: 16 pages * 32 bytes.
+pub type Page = [u8; 32];
+
+/// Information page: 512 bytes
+pub type InfPage = [u8; 512];
 
-/// # Instructions Table
+// # Instructions Table
 
 #[repr(u8)]
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Instruction {
     /// 1 = Collect finger image
     GenImg = 0x01,
@@ -1880,6 +3859,52 @@ impl From<Instruction> for u8 {
     }
 }
 
+impl TryFrom<u8> for Instruction {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(Self::GenImg),
+            0x02 => Ok(Self::GenChar),
+            0x03 => Ok(Self::Match),
+            0x04 => Ok(Self::Search),
+            0x05 => Ok(Self::RegModel),
+            0x06 => Ok(Self::Store),
+            0x07 => Ok(Self::LoadChar),
+            0x08 => Ok(Self::UpChar),
+            0x09 => Ok(Self::DownChar),
+            0x0A => Ok(Self::UpImage),
+            0x0B => Ok(Self::DownImage),
+            0x0C => Ok(Self::DeleteChar),
+            0x0D => Ok(Self::Empty),
+            0x0E => Ok(Self::SetSysPara),
+            0x0F => Ok(Self::ReadSysPara),
+            0x12 => Ok(Self::SetPwd),
+            0x13 => Ok(Self::VfyPwd),
+            0x14 => Ok(Self::GetRandomCode),
+            0x15 => Ok(Self::SetAdder),
+            0x16 => Ok(Self::ReadInfPage),
+            0x17 => Ok(Self::Control),
+            0x18 => Ok(Self::WriteNotepad),
+            0x19 => Ok(Self::ReadNotepad),
+            0x1D => Ok(Self::TempleteNum),
+            0x1F => Ok(Self::ReadIndexTable),
+            0x28 => Ok(Self::GetImageEx),
+            0x30 => Ok(Self::Cancel),
+            0x31 => Ok(Self::AutoEnroll),
+            0x32 => Ok(Self::AutoIdentify),
+            0x35 => Ok(Self::AuraLedConfig),
+            0x36 => Ok(Self::CheckSensor),
+            0x39 => Ok(Self::GetAlgVer),
+            0x3A => Ok(Self::GetFwVer),
+            0x3C => Ok(Self::ReadProdInfo),
+            0x3D => Ok(Self::SoftRst),
+            0x40 => Ok(Self::HandShake),
+            _ => Err(value),
+        }
+    }
+}
+
 // Checksum is calculated on 'length (2 bytes) + data (??)'.
 pub fn compute_checksum(buf: Vec<u8, 256>) -> u16 {
     let mut checksum = 0u16;
@@ -1887,9 +3912,9 @@ pub fn compute_checksum(buf: Vec<u8, 256>) -> u16 {
     let check_end = buf.len();
     let checked_bytes = &buf[6..check_end];
     for byte in checked_bytes {
-        checksum += (*byte) as u16;
+        checksum = checksum.wrapping_add(*byte as u16);
     }
-    return checksum;
+    checksum
 }
 
 #[cfg(test)]
@@ -1901,4 +3926,23 @@ mod tests {
         let vfy_pwd = Payload::VfyPwd(0xFFFFFFFF);
         assert_eq!(vfy_pwd.len(), 4)
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parser_reports_address_on_the_wire() {
+        let frame = Frame {
+            address: 0xCAFE_BABE,
+            identifier: Identifier::Acknowledge,
+            contents: Vec::from_slice(&[0x00]).unwrap(),
+        };
+        let mut parser = Parser::new(ADDRESS);
+        parser.accept_next_address(0xCAFE_BABE);
+
+        let parsed = frame
+            .as_bytes()
+            .unwrap()
+            .into_iter()
+            .find_map(|byte| parser.push(byte));
+
+        assert_eq!(parsed, Some(Ok(frame)));
+    }
+}
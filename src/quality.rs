@@ -0,0 +1,160 @@
+//! Host-side assessment of a fingerprint image before GenChar.
+//!
+//! GenImg takes whatever is on the sensor, and only GenChar finds out the image was too poor for a character file
+//! (0x06, 0x07). Assessing the image uploaded with UpImage first tells the user what to do about it, without spending a GenChar.
+//! The image is looked at in blocks of 16*16 pixels: a block belongs to the finger when it is dark or has ridges in it.
+
+use heapless::Vec;
+
+use crate::{ImageData, IMAGE_SIDE};
+
+const BLOCK: usize = 16;
+const BLOCKS: usize = IMAGE_SIDE / BLOCK;
+
+/// Blocks with a lower mean gray level are part of the finger; the sensor's background is light.
+const BACKGROUND: u8 = 0xC0;
+/// Blocks with ridges in them deviate from their mean by at least this much, on average.
+const RIDGES: u8 = 12;
+
+/// Below this much coverage, in percent, there are too few character points.
+const COVERAGE_MIN: u8 = 35;
+/// Below this contrast, ridges and valleys are hard to tell apart.
+const CONTRAST_MIN: u8 = 40;
+/// A faint finger darker than this is wet, one lighter than `DRY` is dry.
+const WET: u8 = 0x60;
+const DRY: u8 = 0xA0;
+/// How far the finger may be off center, in pixels.
+const OFFSET_MAX: i16 = 24;
+
+/// What the image tells about the finger on the sensor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quality {
+    /// How much of the image the finger covers, in percent.
+    pub coverage: u8,
+    /// How well ridges stand out from valleys: twice the mean deviation from the gray level of the block, 0-255.
+    pub contrast: u8,
+    /// Mean gray level of the finger. Dark is wet, light is dry.
+    pub brightness: u8,
+    /// Where the center of the finger is, relative to the center of the image, in pixels. Positive is right and down.
+    pub offset: (i16, i16),
+}
+
+/// What to tell the user so the next image is better.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hint {
+    /// There is no finger on the sensor.
+    PlaceFinger,
+    /// Too little of the finger touches the sensor, or too faintly.
+    PressHarder,
+    /// Dark, smeared ridges: the finger is wet or pressed too hard.
+    TooWet,
+    /// Light, broken ridges: the finger is dry.
+    TooDry,
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+}
+
+impl Quality {
+    pub fn assess(image: &ImageData) -> Self {
+        let mut blocks = 0u32;
+        let mut brightness = 0u32;
+        let mut contrast = 0u32;
+        let (mut x, mut y) = (0u32, 0u32);
+
+        for row in 0..BLOCKS {
+            for column in 0..BLOCKS {
+                let pixels = || {
+                    (0..BLOCK).flat_map(move |line| {
+                        let start = (row * BLOCK + line) * IMAGE_SIDE + column * BLOCK;
+                        image[start..start + BLOCK]
+                            .iter()
+                            .map(|&pixel| pixel as u32)
+                    })
+                };
+                let mean = pixels().sum::<u32>() / (BLOCK * BLOCK) as u32;
+                let deviation = pixels().map(|pixel| pixel.abs_diff(mean)).sum::<u32>()
+                    / (BLOCK * BLOCK) as u32;
+                if mean >= BACKGROUND as u32 && deviation < RIDGES as u32 {
+                    continue;
+                }
+
+                blocks += 1;
+                brightness += mean;
+                contrast += deviation;
+                x += (column * BLOCK + BLOCK / 2) as u32;
+                y += (row * BLOCK + BLOCK / 2) as u32;
+            }
+        }
+
+        if blocks == 0 {
+            return Self {
+                coverage: 0,
+                contrast: 0,
+                brightness: 0xFF,
+                offset: (0, 0),
+            };
+        }
+        let center = (IMAGE_SIDE / 2) as i16;
+        Self {
+            coverage: (blocks * 100 / (BLOCKS * BLOCKS) as u32) as u8,
+            contrast: (contrast * 2 / blocks).min(255) as u8,
+            brightness: (brightness / blocks) as u8,
+            offset: ((x / blocks) as i16 - center, (y / blocks) as i16 - center),
+        }
+    }
+
+    /// Overall score, 0-100: the worst of coverage, contrast and centering, each scaled so that 100 is comfortably good.
+    pub fn score(&self) -> u8 {
+        let coverage = self.coverage as u32 * 100 / 60;
+        let contrast = self.contrast as u32 * 100 / 80;
+        let distance = self
+            .offset
+            .0
+            .unsigned_abs()
+            .max(self.offset.1.unsigned_abs()) as u32;
+        let centering = 100u32.saturating_sub(distance * 100 / (IMAGE_SIDE as u32 / 2));
+        coverage.min(contrast).min(centering).min(100) as u8
+    }
+
+    /// What would make the image better, most important first. Empty when the image is good enough for GenChar.
+    pub fn hints(&self) -> Vec<Hint, 4> {
+        let mut hints = Vec::new();
+        if self.coverage == 0 {
+            let _ = hints.push(Hint::PlaceFinger);
+            return hints;
+        }
+
+        if self.contrast < CONTRAST_MIN {
+            let hint = match self.brightness {
+                brightness if brightness < WET => Hint::TooWet,
+                brightness if brightness > DRY => Hint::TooDry,
+                _ => Hint::PressHarder,
+            };
+            let _ = hints.push(hint);
+        }
+        if self.coverage < COVERAGE_MIN && hints.is_empty() {
+            let _ = hints.push(Hint::PressHarder);
+        }
+
+        // Image rows run top to bottom: a finger below the center has to move up.
+        let (x, y) = self.offset;
+        if y > OFFSET_MAX {
+            let _ = hints.push(Hint::MoveUp);
+        } else if y < -OFFSET_MAX {
+            let _ = hints.push(Hint::MoveDown);
+        }
+        if x > OFFSET_MAX {
+            let _ = hints.push(Hint::MoveLeft);
+        } else if x < -OFFSET_MAX {
+            let _ = hints.push(Hint::MoveRight);
+        }
+        hints
+    }
+
+    /// Whether the image is worth a GenChar.
+    pub fn is_acceptable(&self) -> bool {
+        self.hints().is_empty()
+    }
+}
@@ -0,0 +1,981 @@
+//! A software R503 that plugs into `Driver` in place of the UART.
+//!
+//! Commands are decoded and answered the way the manual describes, against an in-memory module:
+//! fingerprint library, six CharBuffers, the ImageBuffer, notepad, password, address and system parameters.
+//! Fingers are modelled by `Finger`: images of the same finger produce the same features, and so match each other.
+
+use std::boxed::Box;
+use std::collections::{HashMap, VecDeque};
+use std::vec::Vec;
+
+use core::convert::Infallible;
+use embedded_io_async::{ErrorType, Read, Write};
+
+use crate::{
+    Address, BaudRate, Color, ConfirmationCode, Frame, FrameError, Identifier, ImageData,
+    Instruction, LightPattern, PackedImage, PacketLength, Page, Parser, Password, SecurityLevel,
+    TemplateData, ADDRESS, IMAGE_SIDE, PACKED_IMAGE_SIZE, PASSWORD, READY,
+};
+
+/// Storage capacity: 200
+const LIBRARY_SIZE: u16 = 200;
+/// Below this much coverage there aren't enough character points to generate a character file.
+const MIN_COVERAGE: u8 = 30;
+/// Marks template data produced by the simulator.
+const TEMPLATE_MAGIC: [u8; 4] = *b"SIMT";
+
+/// A finger resting on the simulated sensor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Finger {
+    /// Fingers with the same id match each other.
+    pub id: u32,
+    /// How much of the sensing array the finger covers, in percent.
+    pub coverage: u8,
+}
+
+impl Finger {
+    /// A finger pressed properly on the sensor.
+    pub fn new(id: u32) -> Self {
+        Self { id, coverage: 80 }
+    }
+}
+
+/// What the ImageBuffer holds: the pixels, and the finger they came from.
+struct Image {
+    pixels: Box<ImageData>,
+    /// `None` for images downloaded with DownImage that the sensor didn't capture.
+    finger: Option<Finger>,
+}
+
+/// Where the data packets following DownChar or DownImage go.
+enum Download {
+    Char(usize),
+    Image,
+}
+
+/// Emulated R503 fingerprint module.
+pub struct Simulator {
+    parser: Parser,
+    output: VecDeque<u8>,
+    download: Option<(Download, Vec<u8>)>,
+
+    address: Address,
+    password: Password,
+    password_verified: bool,
+    baud_rate: BaudRate,
+    security_level: SecurityLevel,
+    packet_length: PacketLength,
+    /// Parameters only take effect after the module is powered on again.
+    active_packet_length: PacketLength,
+    pass: bool,
+
+    library: Vec<Option<Box<TemplateData>>>,
+    char_buffers: [Option<Box<TemplateData>>; 6],
+    image: Option<Image>,
+    notepad: [Page; 16],
+    random: u32,
+    aura_led: Option<(LightPattern, Color)>,
+    /// Fingers behind the images the sensor captured, by hash of the image as UpImage sends it,
+    /// so a downloaded image is recognised as the finger it was taken from.
+    captures: HashMap<u32, Finger>,
+
+    finger: Option<Finger>,
+    touches: VecDeque<Option<Finger>>,
+    /// Instruction whose flash write fails.
+    flash_fault: Option<Instruction>,
+    /// ReadSysPara reports the module busy this many more times.
+    busy_reads: usize,
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Simulator {
+    /// A module with factory settings, that was just powered on.
+    pub fn new() -> Self {
+        let mut simulator = Self {
+            parser: Parser::new(ADDRESS),
+            output: VecDeque::new(),
+            download: None,
+            address: ADDRESS,
+            password: PASSWORD,
+            password_verified: false,
+            baud_rate: BaudRate::default(),
+            security_level: SecurityLevel::Level3,
+            packet_length: PacketLength::Bytes128,
+            active_packet_length: PacketLength::Bytes128,
+            pass: false,
+            library: (0..LIBRARY_SIZE).map(|_| None).collect(),
+            char_buffers: Default::default(),
+            image: None,
+            notepad: [[0; 32]; 16],
+            random: 0x2545_F491,
+            aura_led: None,
+            captures: HashMap::new(),
+            finger: None,
+            touches: VecDeque::new(),
+            flash_fault: None,
+            busy_reads: 0,
+        };
+        simulator.power_cycle();
+        simulator
+    }
+
+    /// Cuts the power and turns it back on: buffers are lost, new system parameters take effect,
+    /// and the module sends the 0x55 ready byte.
+    pub fn power_cycle(&mut self) {
+        self.output.clear();
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.parser = Parser::new(self.address);
+        self.download = None;
+        self.password_verified = false;
+        self.active_packet_length = self.packet_length;
+        self.pass = false;
+        self.char_buffers = Default::default();
+        self.image = None;
+        self.output.push_back(READY);
+    }
+
+    /// Whether everything the module sent was read.
+    pub fn output_is_empty(&self) -> bool {
+        self.output.is_empty()
+    }
+
+    /// Puts a finger on the sensor.
+    pub fn place_finger(&mut self, finger: Finger) {
+        self.finger = Some(finger);
+    }
+
+    /// Takes the finger off the sensor.
+    pub fn lift_finger(&mut self) {
+        self.finger = None;
+    }
+
+    /// Scripts what the sensor sees while the driver is busy: every image capture takes the next
+    /// entry, a finger or none, and leaves it there for the captures after the script runs out.
+    pub fn queue_touches(&mut self, touches: impl IntoIterator<Item = Option<Finger>>) {
+        self.touches.extend(touches);
+    }
+
+    /// Makes the next `reads` ReadSysPara report the module busy.
+    pub fn keep_busy(&mut self, reads: usize) {
+        self.busy_reads = reads;
+    }
+
+    /// Makes `instruction` fail with ErrorWhenWritingFlash, leaving the flash as it was. `None` repairs the module.
+    pub fn set_flash_fault(&mut self, instruction: Option<Instruction>) {
+        self.flash_fault = instruction;
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    pub fn password(&self) -> Password {
+        self.password
+    }
+
+    pub fn baud_rate(&self) -> BaudRate {
+        self.baud_rate
+    }
+
+    pub fn security_level(&self) -> SecurityLevel {
+        self.security_level
+    }
+
+    /// The data packet length stored in flash, which takes effect on power-on or SoftRst.
+    pub fn packet_length(&self) -> PacketLength {
+        self.packet_length
+    }
+
+    /// The data packet length the module sends data in until it starts again.
+    pub fn active_packet_length(&self) -> PacketLength {
+        self.active_packet_length
+    }
+
+    /// The template stored at `model_id`, if any.
+    pub fn template(&self, model_id: u16) -> Option<&TemplateData> {
+        self.library.get(model_id as usize)?.as_deref()
+    }
+
+    /// Number of templates in the fingerprint library.
+    pub fn template_count(&self) -> u16 {
+        self.library.iter().filter(|slot| slot.is_some()).count() as u16
+    }
+
+    pub fn notepad(&self, page: usize) -> Option<&Page> {
+        self.notepad.get(page)
+    }
+
+    /// The last pattern and color set with AuraLedConfig.
+    pub fn aura_led(&self) -> Option<(LightPattern, Color)> {
+        self.aura_led
+    }
+
+    /// Template data as the simulated feature extractor would produce it for `finger`.
+    pub fn template_for(finger: Finger) -> TemplateData {
+        Features {
+            finger: finger.id,
+            coverage: finger.coverage,
+            samples: 1,
+        }
+        .template()
+    }
+
+    /// The image the simulated sensor captures for `finger`, 256 gray levels.
+    pub fn image_for(finger: Finger) -> Box<ImageData> {
+        let mut pixels = Box::new([0xFFu8; IMAGE_SIDE * IMAGE_SIDE]);
+
+        // A disc of ridges, as big as the coverage asks for.
+        let center = IMAGE_SIDE as f32 / 2.0;
+        let area = finger.coverage.min(100) as f32 / 100.0 * (IMAGE_SIDE * IMAGE_SIDE) as f32;
+        let radius = (area / core::f32::consts::PI).sqrt();
+
+        // Ridge direction and spacing come from the finger, so each finger looks different.
+        let angle = (finger.id % 180) as f32 * core::f32::consts::PI / 180.0;
+        let period = 6.0 + (finger.id % 5) as f32;
+        let (sin, cos) = angle.sin_cos();
+
+        for y in 0..IMAGE_SIDE {
+            for x in 0..IMAGE_SIDE {
+                let (dx, dy) = (x as f32 - center, y as f32 - center);
+                if dx * dx + dy * dy > radius * radius {
+                    continue;
+                }
+                let phase = (dx * cos + dy * sin) / period * 2.0 * core::f32::consts::PI;
+                let ridge = phase.sin();
+                pixels[y * IMAGE_SIDE + x] = (0x80 as f32 + 0x60 as f32 * ridge) as u8;
+            }
+        }
+
+        pixels
+    }
+
+    fn reply(&mut self, contents: &[u8]) {
+        self.send(Identifier::Acknowledge, contents);
+    }
+
+    fn reply_code(&mut self, code: ConfirmationCode) {
+        self.reply(&[code as u8]);
+    }
+
+    fn send(&mut self, identifier: Identifier, contents: &[u8]) {
+        let frame = Frame {
+            address: self.address,
+            identifier,
+            contents: heapless::Vec::from_slice(contents).expect("contents fit a package"),
+        };
+        let bytes = frame.as_bytes().expect("contents fit a package");
+        self.output.extend(bytes);
+    }
+
+    /// Sends `data` as data packets, the way the module does after UpChar, UpImage and ReadInfPage.
+    fn send_data(&mut self, data: &[u8]) {
+        let mut chunks = data.chunks(self.active_packet_length.bytes()).peekable();
+        while let Some(chunk) = chunks.next() {
+            let identifier = match chunks.peek() {
+                Some(_) => Identifier::Data,
+                None => Identifier::End,
+            };
+            self.send(identifier, chunk);
+        }
+    }
+
+    fn next_random(&mut self) -> u32 {
+        // xorshift32
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        self.random
+    }
+
+    fn status_register(&self) -> u16 {
+        let mut register = 0;
+        if self.busy_reads > 0 {
+            register |= 0b0001;
+        }
+        if self.pass {
+            register |= 0b0010;
+        }
+        if self.password_verified {
+            register |= 0b0100;
+        }
+        if self.image.is_some() {
+            register |= 0b1000;
+        }
+        register
+    }
+
+    fn receive(&mut self, frame: Result<Frame, FrameError>) {
+        let frame = match frame {
+            Ok(frame) => frame,
+            // The module doesn't answer packages sent to other addresses.
+            Err(FrameError::Address(_)) => return,
+            Err(_) => return self.reply_code(ConfirmationCode::ErrorReceivingPacket),
+        };
+
+        match frame.identifier {
+            Identifier::Command => self.execute(&frame.contents),
+            Identifier::Data | Identifier::End => self.download(&frame),
+            Identifier::Acknowledge => {}
+        }
+    }
+
+    fn download(&mut self, frame: &Frame) {
+        let Some((_, data)) = self.download.as_mut() else {
+            return;
+        };
+        data.extend_from_slice(&frame.contents);
+        if frame.identifier != Identifier::End {
+            return;
+        }
+
+        let Some((destination, data)) = self.download.take() else {
+            return;
+        };
+        match destination {
+            Download::Char(buffer) => {
+                let mut template = Box::new([0u8; 1536]);
+                let length = data.len().min(template.len());
+                template[..length].copy_from_slice(&data[..length]);
+                self.char_buffers[buffer] = Some(template);
+            }
+            Download::Image => {
+                let mut packed = [0u8; PACKED_IMAGE_SIZE];
+                let length = data.len().min(packed.len());
+                packed[..length].copy_from_slice(&data[..length]);
+                let packed = PackedImage::new(packed);
+                let mut pixels = Box::new([0u8; IMAGE_SIDE * IMAGE_SIDE]);
+                packed.unpack(&mut pixels);
+                self.image = Some(Image {
+                    pixels,
+                    finger: self.captures.get(&fnv1a(packed.as_bytes())).copied(),
+                });
+            }
+        }
+    }
+
+    fn execute(&mut self, contents: &[u8]) {
+        let Some((&code, parameters)) = contents.split_first() else {
+            return self.reply_code(ConfirmationCode::ErrorReceivingPacket);
+        };
+        self.download = None;
+
+        let Ok(instruction) = Instruction::try_from(code) else {
+            return self.reply_code(ConfirmationCode::UnsupportedCommand);
+        };
+        if self.password != PASSWORD
+            && !self.password_verified
+            && instruction != Instruction::VfyPwd
+        {
+            return self.reply_code(ConfirmationCode::MustVerifyPassword);
+        }
+        if self.flash_fault == Some(instruction) {
+            return self.reply_code(ConfirmationCode::ErrorWhenWritingFlash);
+        }
+
+        let byte = |index: usize| parameters.get(index).copied();
+        let word = |index: usize| Some(u16::from_be_bytes([byte(index)?, byte(index + 1)?]));
+        let long = |index: usize| {
+            Some(u32::from_be_bytes([
+                byte(index)?,
+                byte(index + 1)?,
+                byte(index + 2)?,
+                byte(index + 3)?,
+            ]))
+        };
+
+        use ConfirmationCode as Code;
+        match instruction {
+            Instruction::GenImg | Instruction::GetImageEx => {
+                if let Some(touch) = self.touches.pop_front() {
+                    self.finger = touch;
+                }
+                let Some(finger) = self.finger else {
+                    return self.reply_code(Code::NoFingerOnSensor);
+                };
+                let pixels = Self::image_for(finger);
+                let packed = PackedImage::pack(&pixels);
+                self.captures.insert(fnv1a(packed.as_bytes()), finger);
+                self.image = Some(Image {
+                    pixels,
+                    finger: Some(finger),
+                });
+                if instruction == Instruction::GetImageEx && finger.coverage < MIN_COVERAGE {
+                    return self.reply_code(
+                        Code::FailToGenerateCharacterLacknessOfCharacterPointOrOverSmallness,
+                    );
+                }
+                self.reply_code(Code::Success)
+            }
+            Instruction::GenChar => {
+                let Some(buffer) = byte(0).and_then(char_buffer) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                let Some(image) = &self.image else {
+                    return self.reply_code(Code::FailToGenerateImageLacknessOfValidPrimaryImage);
+                };
+                let (id, coverage) = match image.finger {
+                    Some(finger) => (finger.id, finger.coverage),
+                    None => (fnv1a(&image.pixels[..]), coverage(&image.pixels)),
+                };
+                if coverage < MIN_COVERAGE {
+                    return self.reply_code(
+                        Code::FailToGenerateCharacterLacknessOfCharacterPointOrOverSmallness,
+                    );
+                }
+                self.char_buffers[buffer] = Some(Box::new(
+                    Features {
+                        finger: id,
+                        coverage,
+                        samples: 1,
+                    }
+                    .template(),
+                ));
+                self.reply_code(Code::Success)
+            }
+            Instruction::Match => {
+                let probe = self.char_buffers[0].as_deref().and_then(Features::read);
+                let model = self.char_buffers[1].as_deref().and_then(Features::read);
+                match (probe, model) {
+                    (Some(probe), Some(model)) if probe.finger == model.finger => {
+                        self.pass = true;
+                        let [high, low] = score(probe.coverage).to_be_bytes();
+                        self.reply(&[Code::Success as u8, high, low]);
+                    }
+                    _ => {
+                        self.pass = false;
+                        self.reply(&[Code::FailFingerDoesntMatch as u8, 0, 0]);
+                    }
+                }
+            }
+            Instruction::Search => {
+                let (Some(buffer), Some(start), Some(num)) =
+                    (byte(0).and_then(char_buffer), word(1), word(3))
+                else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                if start >= LIBRARY_SIZE {
+                    return self.reply_code(Code::AddressingPageIDIsBeyoundTheFingerLibary);
+                }
+                let probe = self.char_buffers[buffer]
+                    .as_deref()
+                    .and_then(Features::read);
+                let end = start.saturating_add(num).min(LIBRARY_SIZE);
+                let found = probe.and_then(|probe| {
+                    (start..end)
+                        .find(|id| {
+                            let model = self.library[*id as usize]
+                                .as_deref()
+                                .and_then(Features::read);
+                            model.is_some_and(|model| model.finger == probe.finger)
+                        })
+                        .map(|id| (id, score(probe.coverage)))
+                });
+                self.pass = found.is_some();
+                match found {
+                    Some((id, score)) => {
+                        let [id_high, id_low] = id.to_be_bytes();
+                        let [high, low] = score.to_be_bytes();
+                        self.reply(&[Code::Success as u8, id_high, id_low, high, low]);
+                    }
+                    None => self.reply(&[Code::FailToFindMatchingFinger as u8, 0, 0, 0, 0]),
+                }
+            }
+            Instruction::RegModel => {
+                // Character files merge into a template; templates already merged are left out.
+                let samples: Vec<_> = self
+                    .char_buffers
+                    .iter()
+                    .flatten()
+                    .filter_map(|buffer| Features::read(buffer))
+                    .filter(|sample| sample.samples == 1)
+                    .collect();
+                let Some(first) = samples.first().copied() else {
+                    return self.reply_code(Code::FailToCombineCharacterFiles);
+                };
+                if samples.len() < 2 || samples.iter().any(|sample| sample.finger != first.finger) {
+                    return self.reply_code(Code::FailToCombineCharacterFiles);
+                }
+                let model = Features {
+                    coverage: samples
+                        .iter()
+                        .map(|sample| sample.coverage)
+                        .max()
+                        .unwrap_or(0),
+                    samples: samples.len() as u8,
+                    ..first
+                }
+                .template();
+                for buffer in self.char_buffers.iter_mut() {
+                    *buffer = Some(Box::new(model));
+                }
+                self.reply_code(Code::Success)
+            }
+            Instruction::Store | Instruction::LoadChar => {
+                let (Some(buffer), Some(id)) = (byte(0).and_then(char_buffer), word(1)) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                if id >= LIBRARY_SIZE {
+                    return self.reply_code(Code::AddressingPageIDIsBeyoundTheFingerLibary);
+                }
+                if instruction == Instruction::Store {
+                    let Some(template) = self.char_buffers[buffer].clone() else {
+                        return self.reply_code(Code::FingerTemplateEmpty);
+                    };
+                    self.library[id as usize] = Some(template);
+                } else {
+                    let Some(template) = self.library[id as usize].clone() else {
+                        return self.reply_code(
+                            Code::ErrorWhenReadingTemplateFromLibararORTemplateIsInvalid,
+                        );
+                    };
+                    self.char_buffers[buffer] = Some(template);
+                }
+                self.reply_code(Code::Success)
+            }
+            Instruction::UpChar => {
+                let Some(buffer) = byte(0).and_then(char_buffer) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                let Some(template) = self.char_buffers[buffer].clone() else {
+                    return self.reply_code(Code::ErrorWhenUploadingTemplate);
+                };
+                self.reply_code(Code::Success);
+                self.send_data(&template[..]);
+            }
+            Instruction::DownChar => {
+                let Some(buffer) = byte(0).and_then(char_buffer) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                self.reply_code(Code::Success);
+                self.download = Some((Download::Char(buffer), Vec::new()));
+            }
+            Instruction::UpImage => {
+                let Some(image) = &self.image else {
+                    return self.reply_code(Code::ErrorWhenUploadingImage);
+                };
+                let packed = PackedImage::pack(&image.pixels);
+                self.reply_code(Code::Success);
+                self.send_data(packed.as_bytes());
+            }
+            Instruction::DownImage => {
+                self.reply_code(Code::Success);
+                self.download = Some((Download::Image, Vec::new()));
+            }
+            Instruction::DeleteChar => {
+                let (Some(start), Some(num)) = (word(0), word(2)) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                if start as usize + num as usize > LIBRARY_SIZE as usize {
+                    return self.reply_code(Code::FailToDeleteTheTemplate);
+                }
+                for slot in &mut self.library[start as usize..(start + num) as usize] {
+                    *slot = None;
+                }
+                self.reply_code(Code::Success)
+            }
+            Instruction::Empty => {
+                self.library.iter_mut().for_each(|slot| *slot = None);
+                self.reply_code(Code::Success)
+            }
+            Instruction::SetSysPara => {
+                let (Some(parameter), Some(value)) = (byte(0), byte(1)) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                let valid = match parameter {
+                    4 => baud_rate(value).map(|rate| self.baud_rate = rate).is_some(),
+                    5 => security_level(value)
+                        .map(|level| self.security_level = level)
+                        .is_some(),
+                    6 => PacketLength::try_from(value as u16)
+                        .map(|length| self.packet_length = length)
+                        .is_ok(),
+                    _ => return self.reply_code(Code::InvalidRegisterNumber),
+                };
+                match valid {
+                    true => self.reply_code(Code::Success),
+                    false => self.reply_code(Code::IncorrectConfigurationOfRegister),
+                }
+            }
+            Instruction::ReadSysPara => {
+                let mut reply = std::vec![Code::Success as u8];
+                reply.extend(self.status_register().to_be_bytes());
+                self.busy_reads = self.busy_reads.saturating_sub(1);
+                reply.extend(0x0009u16.to_be_bytes());
+                reply.extend(LIBRARY_SIZE.to_be_bytes());
+                reply.extend((self.security_level as u16).to_be_bytes());
+                reply.extend(self.address.to_be_bytes());
+                reply.extend((self.packet_length as u16).to_be_bytes());
+                reply.extend((self.baud_rate as u16).to_be_bytes());
+                self.reply(&reply);
+            }
+            Instruction::SetPwd => {
+                let Some(password) = long(0) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                self.password = password;
+                self.reply_code(Code::Success)
+            }
+            Instruction::VfyPwd => {
+                self.password_verified = long(0) == Some(self.password);
+                match self.password_verified {
+                    true => self.reply_code(Code::Success),
+                    false => self.reply_code(Code::WrongPassword),
+                }
+            }
+            Instruction::GetRandomCode => {
+                let random = self.next_random().to_be_bytes();
+                self.reply(&[
+                    Code::Success as u8,
+                    random[0],
+                    random[1],
+                    random[2],
+                    random[3],
+                ]);
+            }
+            Instruction::SetAdder => {
+                let Some(address) = long(0) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                // The acknowledge packet already carries the new address.
+                self.address = address;
+                self.parser = Parser::new(address);
+                self.reply_code(Code::Success)
+            }
+            Instruction::ReadInfPage => {
+                let mut page = [0u8; 512];
+                page[..8].copy_from_slice(b"R503 SIM");
+                self.reply_code(Code::Success);
+                self.send_data(&page);
+            }
+            Instruction::WriteNotepad => {
+                let Some(page) = byte(0) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                let (Some(slot), Some(content)) =
+                    (self.notepad.get_mut(page as usize), parameters.get(1..33))
+                else {
+                    return self.reply_code(Code::WrongNotepadPageNumber);
+                };
+                slot.copy_from_slice(content);
+                self.reply_code(Code::Success)
+            }
+            Instruction::ReadNotepad => {
+                let Some(page) = byte(0)
+                    .and_then(|page| self.notepad.get(page as usize))
+                    .copied()
+                else {
+                    return self.reply_code(Code::WrongNotepadPageNumber);
+                };
+                let mut reply = std::vec![Code::Success as u8];
+                reply.extend(page);
+                self.reply(&reply);
+            }
+            Instruction::TempleteNum => {
+                let [high, low] = self.template_count().to_be_bytes();
+                self.reply(&[Code::Success as u8, high, low]);
+            }
+            Instruction::ReadIndexTable => {
+                let Some(page) = byte(0).filter(|page| *page < 4) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                let mut reply = std::vec![0u8; 33];
+                reply[0] = Code::Success as u8;
+                for (id, slot) in self.library.iter().enumerate() {
+                    if slot.is_some() && id / 256 == page as usize {
+                        reply[1 + (id % 256) / 8] |= 1 << (id % 8);
+                    }
+                }
+                self.reply(&reply);
+            }
+            Instruction::Cancel | Instruction::CheckSensor | Instruction::HandShake => {
+                self.reply_code(Code::Success)
+            }
+            Instruction::AutoEnroll => self.auto_enroll(parameters),
+            Instruction::AutoIdentify => self.auto_identify(parameters),
+            Instruction::AuraLedConfig => {
+                let (Some(pattern), Some(color)) = (
+                    byte(0).and_then(|pattern| LightPattern::try_from(pattern).ok()),
+                    byte(2).and_then(|color| Color::try_from(color).ok()),
+                ) else {
+                    return self.reply_code(Code::ErrorReceivingPacket);
+                };
+                self.aura_led = Some((pattern, color));
+                self.reply_code(Code::Success)
+            }
+            Instruction::GetAlgVer | Instruction::GetFwVer => {
+                let mut reply = std::vec![0u8; 33];
+                reply[1..11].copy_from_slice(b"SIM-1.0.00");
+                self.reply(&reply);
+            }
+            Instruction::ReadProdInfo => {
+                let mut reply = std::vec![0u8; 47];
+                reply[1..5].copy_from_slice(b"R503");
+                reply[17..21].copy_from_slice(b"0001");
+                reply[21..29].copy_from_slice(b"00000001");
+                reply[29..31].copy_from_slice(&[1, 0]);
+                reply[31..34].copy_from_slice(b"SIM");
+                reply[39..41].copy_from_slice(&(IMAGE_SIDE as u16).to_be_bytes());
+                reply[41..43].copy_from_slice(&(IMAGE_SIDE as u16).to_be_bytes());
+                reply[43..45].copy_from_slice(&1536u16.to_be_bytes());
+                reply[45..47].copy_from_slice(&LIBRARY_SIZE.to_be_bytes());
+                self.reply(&reply);
+            }
+            Instruction::SoftRst => {
+                self.reply_code(Code::Success);
+                self.reset();
+            }
+            _ => self.reply_code(Code::UnsupportedCommand),
+        }
+    }
+
+    /// AutoEnroll: collects six images of the finger on the sensor, merges them and stores the template.
+    fn auto_enroll(&mut self, parameters: &[u8]) {
+        use ConfirmationCode as Code;
+        let [id, allow_cover_id, allow_duplicate, return_in_critical, _ask_finger_to_leave] =
+            parameters
+                .get(..5)
+                .and_then(|parameters| <[u8; 5]>::try_from(parameters).ok())
+                .unwrap_or_default();
+        let critical = return_in_critical != 0;
+
+        // 0xC8-0xFF: the module picks the first empty location.
+        let id = match id {
+            0..=0xC7 => Some(id as u16),
+            _ => (0..LIBRARY_SIZE).find(|id| self.library[*id as usize].is_none()),
+        };
+        let Some(id) = id else {
+            return self.reply(&[Code::FingerPrintLibaryFull as u8, 0x00, 0x00]);
+        };
+        if id >= LIBRARY_SIZE {
+            return self.reply(&[
+                Code::AddressingPageIDIsBeyoundTheFingerLibary as u8,
+                0x00,
+                id as u8,
+            ]);
+        }
+        if allow_cover_id == 0 && self.library[id as usize].is_some() {
+            return self.reply(&[Code::CommandExecutionFailure as u8, 0x00, id as u8]);
+        }
+
+        for sample in 0..6u8 {
+            let Some(finger) = self.finger else {
+                return self.reply(&[Code::Timeout as u8, sample * 2 + 1, id as u8]);
+            };
+            if critical {
+                self.reply(&[Code::Success as u8, sample * 2 + 1, id as u8]);
+            }
+            if finger.coverage < MIN_COVERAGE {
+                return self.reply(&[
+                    Code::FailToGenerateCharacterLacknessOfCharacterPointOrOverSmallness as u8,
+                    sample * 2 + 2,
+                    id as u8,
+                ]);
+            }
+            if critical {
+                self.reply(&[Code::Success as u8, sample * 2 + 2, id as u8]);
+            }
+        }
+
+        let finger = self.finger.unwrap_or(Finger::new(0));
+        let duplicate = self
+            .library
+            .iter()
+            .flatten()
+            .filter_map(|model| Features::read(model))
+            .any(|model| model.finger == finger.id);
+        if critical {
+            self.reply(&[Code::Success as u8, 0x0D, id as u8]);
+        }
+        if allow_duplicate == 0 && duplicate {
+            return self.reply(&[Code::FingerAlreadyExists as u8, 0x0D, id as u8]);
+        }
+        if critical {
+            self.reply(&[Code::Success as u8, 0x0E, id as u8]);
+        }
+        self.library[id as usize] = Some(Box::new(
+            Features {
+                finger: finger.id,
+                coverage: finger.coverage,
+                samples: 6,
+            }
+            .template(),
+        ));
+        self.reply(&[Code::Success as u8, 0x0F, id as u8]);
+    }
+
+    /// AutoIdentify: collects an image of the finger on the sensor and searches the library for it.
+    fn auto_identify(&mut self, parameters: &[u8]) {
+        use ConfirmationCode as Code;
+        let [_grade, start, num, _times, return_in_critical] = parameters
+            .get(..5)
+            .and_then(|parameters| <[u8; 5]>::try_from(parameters).ok())
+            .unwrap_or_default();
+        let critical = return_in_critical != 0;
+
+        if self.template_count() == 0 {
+            return self.reply(&[Code::FingerLibaryEmpty as u8, 0, 0, 0, 0, 0]);
+        }
+        let Some(finger) = self.finger else {
+            return self.reply(&[Code::Timeout as u8, 0x01, 0, 0, 0, 0]);
+        };
+        if critical {
+            self.reply(&[Code::Success as u8, 0x01, 0, 0, 0, 0]);
+        }
+        if finger.coverage < MIN_COVERAGE {
+            return self.reply(&[
+                Code::FailToGenerateCharacterLacknessOfCharacterPointOrOverSmallness as u8,
+                0x02,
+                0,
+                0,
+                0,
+                0,
+            ]);
+        }
+        if critical {
+            self.reply(&[Code::Success as u8, 0x02, 0, 0, 0, 0]);
+        }
+
+        let end = (start as u16 + num as u16).min(LIBRARY_SIZE);
+        let found = (start as u16..end).find(|id| {
+            let model = self.library[*id as usize]
+                .as_deref()
+                .and_then(Features::read);
+            model.is_some_and(|model| model.finger == finger.id)
+        });
+        self.pass = found.is_some();
+        match found {
+            Some(id) => {
+                let [id_high, id_low] = id.to_be_bytes();
+                let [high, low] = score(finger.coverage).to_be_bytes();
+                self.reply(&[Code::Success as u8, 0x05, id_high, id_low, high, low]);
+            }
+            None => self.reply(&[Code::FailToFindMatchingFinger as u8, 0x05, 0, 0, 0, 0]),
+        }
+    }
+}
+
+impl ErrorType for Simulator {
+    type Error = Infallible;
+}
+
+impl Read for Simulator {
+    /// Hands out whatever the module has sent so far. Returns 0 when the module has nothing to say.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let count = buf.len().min(self.output.len());
+        for (slot, byte) in buf.iter_mut().zip(self.output.drain(..count)) {
+            *slot = byte;
+        }
+        Ok(count)
+    }
+}
+
+impl Write for Simulator {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        for byte in buf {
+            if let Some(frame) = self.parser.push(*byte) {
+                self.receive(frame);
+            }
+        }
+        Ok(buf.len())
+    }
+}
+
+/// Maps a CharBufferID (1-6) to an index into `char_buffers`.
+fn char_buffer(buffer_id: u8) -> Option<usize> {
+    match buffer_id {
+        1..=6 => Some(buffer_id as usize - 1),
+        _ => None,
+    }
+}
+
+fn baud_rate(value: u8) -> Option<BaudRate> {
+    match value {
+        1 => Some(BaudRate::Rate9600),
+        2 => Some(BaudRate::Rate19200),
+        4 => Some(BaudRate::Rate38400),
+        6 => Some(BaudRate::Rate57600),
+        12 => Some(BaudRate::Rate115200),
+        _ => None,
+    }
+}
+
+fn security_level(value: u8) -> Option<SecurityLevel> {
+    match value {
+        1 => Some(SecurityLevel::Level1),
+        2 => Some(SecurityLevel::Level2),
+        3 => Some(SecurityLevel::Level3),
+        4 => Some(SecurityLevel::Level4),
+        5 => Some(SecurityLevel::Level5),
+        _ => None,
+    }
+}
+
+/// What simulated template data is made of.
+#[derive(Clone, Copy)]
+struct Features {
+    finger: u32,
+    coverage: u8,
+    /// 1 for a character file, the number of merged character files for a template.
+    samples: u8,
+}
+
+impl Features {
+    /// A marker, the finger id, its coverage and the number of samples. The rest is filled with bytes
+    /// derived from the finger, so templates of different fingers differ all over.
+    fn template(self) -> TemplateData {
+        let mut template = [0u8; 1536];
+        template[..4].copy_from_slice(&TEMPLATE_MAGIC);
+        template[4..8].copy_from_slice(&self.finger.to_be_bytes());
+        template[8] = self.coverage;
+        template[9] = self.samples;
+
+        let mut state = self.finger | 1;
+        for byte in &mut template[16..] {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            *byte = state as u8;
+        }
+        template
+    }
+
+    /// `None` for data the simulator didn't produce.
+    fn read(template: &TemplateData) -> Option<Self> {
+        if template[..4] != TEMPLATE_MAGIC {
+            return None;
+        }
+        Some(Self {
+            finger: u32::from_be_bytes([template[4], template[5], template[6], template[7]]),
+            coverage: template[8],
+            samples: template[9],
+        })
+    }
+}
+
+/// The better the finger covers the sensor, the better it scores.
+fn score(coverage: u8) -> u16 {
+    50 + 2 * coverage.min(100) as u16
+}
+
+/// Percentage of the image that isn't background.
+fn coverage(pixels: &ImageData) -> u8 {
+    let covered = pixels.iter().filter(|pixel| **pixel < 0xF0).count();
+    (covered * 100 / pixels.len()) as u8
+}
+
+fn fnv1a(bytes: &[u8]) -> u32 {
+    let mut hash: u32 = 0x811C_9DC5;
+    for byte in bytes {
+        hash ^= *byte as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+    }
+    hash
+}
@@ -1,4 +1,11 @@
+use core::convert::Infallible;
+use embassy_futures::select::{select, Either};
+use embassy_sync::blocking_mutex::raw::NoopRawMutex;
 use pretty_hex::*;
+use r503::backup::{Archive, ArchiveError, Entry};
+use r503::bus::SensorBus;
+use r503::quality::{Hint, Quality};
+use r503::simulator::{Finger, Simulator};
 use r503::*;
 
 #[test]
@@ -23,7 +30,8 @@ fn checksum_templete_packet() {
         Identifier::Command,
         Instruction::TempleteNum,
         Payload::TempleteNum,
-    );
+    )
+    .unwrap();
 
     let hexcfg = HexConfig {
         title: true,
@@ -32,7 +40,10 @@ fn checksum_templete_packet() {
         ..HexConfig::default()
     };
 
-    println!("Package: {:?}", &package.as_bytes().hex_conf(hexcfg));
+    println!(
+        "Package: {:?}",
+        &package.as_bytes().unwrap().hex_conf(hexcfg)
+    );
 
     assert_eq!(package.get_checksum(), 0x0021);
 }
@@ -52,3 +63,1927 @@ fn checksum_gen_img() {
 
     assert_eq!(checksum, 0x0005);
 }
+
+/// Plays back canned module replies and records everything the driver writes.
+struct MockUart {
+    rx: std::collections::VecDeque<u8>,
+    tx: std::vec::Vec<u8>,
+}
+
+impl MockUart {
+    fn new(rx: &[u8]) -> Self {
+        Self {
+            rx: rx.iter().copied().collect(),
+            tx: std::vec::Vec::new(),
+        }
+    }
+}
+
+impl embedded_io_async::ErrorType for MockUart {
+    type Error = Infallible;
+}
+
+impl embedded_io_async::Read for MockUart {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let mut count = 0;
+        while count < buf.len() {
+            match self.rx.pop_front() {
+                Some(byte) => buf[count] = byte,
+                None => break,
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+impl embedded_io_async::Write for MockUart {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+#[test]
+fn driver_gen_img() {
+    // From the manual, GenImg acknowledge with "Finger collection successs"
+    let uart = MockUart::new(&[
+        0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x03, 0x00, 0x00, 0x0A,
+    ]);
+    let mut driver = Driver::new(uart);
+
+    embassy_futures::block_on(driver.gen_img()).unwrap();
+
+    assert_eq!(
+        driver.uart.tx,
+        [0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x03, 0x01, 0x00, 0x05]
+    );
+}
+
+#[test]
+fn driver_templete_num() {
+    // TempleteNum acknowledge carrying a template count of 0x0010
+    let uart = MockUart::new(&[
+        0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x05, 0x00, 0x00, 0x10, 0x00, 0x1C,
+    ]);
+    let mut driver = Driver::new(uart);
+
+    let count = embassy_futures::block_on(driver.templete_num()).unwrap();
+
+    assert_eq!(count, 0x0010);
+}
+
+#[test]
+fn driver_search() {
+    // Search acknowledge: found ModelID 0x0005 with a MatchScore of 0x0060
+    let uart = MockUart::new(&[
+        0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x07, 0x00, 0x00, 0x05, 0x00, 0x60, 0x00,
+        0x73,
+    ]);
+    let mut driver = Driver::new(uart);
+
+    let start = ModelId::new(0, 200).unwrap();
+    let result =
+        embassy_futures::block_on(driver.search(BufferID::CharBuffer1, start, 200)).unwrap();
+
+    assert_eq!(result.page_id, ModelId::new(0x0005, 200).unwrap());
+    assert_eq!(result.score, 0x0060);
+}
+
+#[test]
+fn driver_missing_parameters() {
+    // A TempleteNum acknowledge without the template number
+    let uart = MockUart::new(&[
+        0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x03, 0x00, 0x00, 0x0A,
+    ]);
+    let mut driver = Driver::new(uart);
+
+    let result = embassy_futures::block_on(driver.templete_num());
+
+    assert_eq!(result, Err(Error::MissingParameters));
+}
+
+#[test]
+fn driver_confirmation_error() {
+    // GenImg acknowledge with "Can't detect finger"
+    let uart = MockUart::new(&[
+        0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x03, 0x02, 0x00, 0x0C,
+    ]);
+    let mut driver = Driver::new(uart);
+
+    let result = embassy_futures::block_on(driver.gen_img());
+
+    assert_eq!(
+        result,
+        Err(Error::Confirmation(ConfirmationCode::NoFingerOnSensor))
+    );
+}
+
+#[test]
+fn driver_no_reply() {
+    let mut driver = Driver::new(MockUart::new(&[]));
+
+    let result = embassy_futures::block_on(driver.handshake());
+
+    assert_eq!(result, Err(Error::UnexpectedEof));
+}
+
+fn parse(parser: &mut Parser, bytes: &[u8]) -> std::vec::Vec<Result<Frame, FrameError>> {
+    bytes.iter().filter_map(|byte| parser.push(*byte)).collect()
+}
+
+#[test]
+fn parser_resynchronizes_on_header() {
+    let mut parser = Parser::default();
+
+    // The 0x55 ready byte and some line noise, then a GenImg acknowledge.
+    let frames = parse(
+        &mut parser,
+        &[
+            0x55, 0xEF, 0x00, 0xEF, 0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x03, 0x02,
+            0x00, 0x0C,
+        ],
+    );
+
+    assert_eq!(frames.len(), 1);
+    let frame = frames[0].as_ref().unwrap();
+    assert_eq!(frame.identifier, Identifier::Acknowledge);
+    assert_eq!(
+        frame.confirmation_code(),
+        ConfirmationCode::NoFingerOnSensor
+    );
+    assert!(frame.parameters().is_empty());
+}
+
+#[test]
+fn parser_wants_never_passes_the_package() {
+    let mut parser = Parser::default();
+    assert_eq!(parser.wants(), 9);
+
+    parse(
+        &mut parser,
+        &[0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x02, 0x00, 0x06],
+    );
+    assert_eq!(parser.wants(), 6);
+
+    let frames = parse(&mut parser, &[0x01, 0x02, 0x03, 0x04, 0x00, 0x12]);
+    assert_eq!(frames.len(), 1);
+    let frame = frames[0].as_ref().unwrap();
+    assert_eq!(frame.identifier, Identifier::Data);
+    assert_eq!(&frame.contents[..], &[0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(parser.wants(), 9);
+}
+
+#[test]
+fn parser_rejects_bad_packages() {
+    let mut parser = Parser::default();
+
+    let frames = parse(
+        &mut parser,
+        &[
+            0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x00, 0x03, 0x00, 0x00, 0x0B,
+        ],
+    );
+    assert_eq!(
+        frames,
+        [Err(FrameError::Checksum {
+            expected: 0x000A,
+            received: 0x000B
+        })]
+    );
+
+    let frames = parse(
+        &mut parser,
+        &[0xEF, 0x01, 0x00, 0x00, 0x00, 0x01, 0x07, 0x00, 0x03],
+    );
+    assert_eq!(frames, [Err(FrameError::Address(0x00000001))]);
+
+    let frames = parse(
+        &mut parser,
+        &[0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x05, 0x00, 0x03],
+    );
+    assert_eq!(frames, [Err(FrameError::Identifier(0x05))]);
+
+    let frames = parse(
+        &mut parser,
+        &[0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x07, 0x01, 0x03],
+    );
+    assert_eq!(frames, [Err(FrameError::Length(0x0103))]);
+}
+
+fn frame(identifier: Identifier, contents: &[u8]) -> std::vec::Vec<u8> {
+    Frame {
+        address: ADDRESS,
+        identifier,
+        contents: heapless::Vec::from_slice(contents).unwrap(),
+    }
+    .as_bytes()
+    .unwrap()
+    .to_vec()
+}
+
+#[test]
+fn driver_read_inf_page() {
+    // Acknowledge, then the page as three data packets and an end of data packet of 128 bytes.
+    let mut rx = frame(Identifier::Acknowledge, &[0x00]);
+    for (index, identifier) in [
+        Identifier::Data,
+        Identifier::Data,
+        Identifier::Data,
+        Identifier::End,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        rx.extend(frame(identifier, &[index as u8; 128]));
+    }
+    let mut driver = Driver::new(MockUart::new(&rx));
+
+    let mut page = [0u8; 512];
+    let received = embassy_futures::block_on(driver.read_inf_page(&mut page)).unwrap();
+
+    assert_eq!(received, 512);
+    assert_eq!(page[0], 0);
+    assert_eq!(page[128], 1);
+    assert_eq!(page[511], 3);
+}
+
+#[test]
+fn driver_up_char_overflow() {
+    let mut rx = frame(Identifier::Acknowledge, &[0x00]);
+    for _ in 0..13 {
+        rx.extend(frame(Identifier::Data, &[0xAA; 128]));
+    }
+    let mut driver = Driver::new(MockUart::new(&rx));
+
+    let mut template = [0u8; 1536];
+    let result = embassy_futures::block_on(driver.up_char(BufferID::CharBuffer1, &mut template));
+
+    assert_eq!(result, Err(Error::BufferOverflow));
+}
+
+#[test]
+fn driver_down_char() {
+    let mut driver = Driver::new(MockUart::new(&frame(Identifier::Acknowledge, &[0x00])));
+
+    let mut template = [0u8; 1536];
+    for (index, byte) in template.iter_mut().enumerate() {
+        *byte = (index / 128) as u8;
+    }
+    embassy_futures::block_on(driver.down_char(BufferID::CharBuffer1, &template)).unwrap();
+
+    // The command, then twelve packets of 128 bytes.
+    let mut expected =
+        std::vec![0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x04, 0x09, 0x01, 0x00, 0x0F,];
+    for index in 0..12u8 {
+        let identifier = match index {
+            11 => Identifier::End,
+            _ => Identifier::Data,
+        };
+        expected.extend(frame(identifier, &[index; 128]));
+    }
+    assert_eq!(driver.uart.tx, expected);
+}
+
+#[test]
+fn model_id_within_library() {
+    assert_eq!(ModelId::new(199, 200).map(ModelId::get), Some(199));
+    assert_eq!(ModelId::new(200, 200), None);
+}
+
+#[test]
+fn driver_store_sends_two_byte_model_id() {
+    let mut driver = Driver::new(MockUart::new(&frame(Identifier::Acknowledge, &[0x00])));
+
+    let model_id = ModelId::new(0x0123, 1500).unwrap();
+    embassy_futures::block_on(driver.store(BufferID::CharBuffer1, model_id)).unwrap();
+
+    assert_eq!(
+        driver.uart.tx,
+        [
+            0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x06, 0x06, 0x01, 0x01, 0x23, 0x00,
+            0x32
+        ]
+    );
+}
+
+fn enroll(driver: &mut Driver<Simulator>, finger: Finger, model_id: ModelId) {
+    embassy_futures::block_on(async {
+        driver.uart.place_finger(finger);
+        driver.gen_img().await.unwrap();
+        driver.gen_char(BufferID::CharBuffer1).await.unwrap();
+        driver.gen_img().await.unwrap();
+        driver.gen_char(BufferID::CharBuffer2).await.unwrap();
+        driver.uart.lift_finger();
+        driver.reg_model().await.unwrap();
+        driver.store(BufferID::CharBuffer1, model_id).await.unwrap();
+    })
+}
+
+#[test]
+fn simulator_enroll_and_search() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(7, 200).unwrap();
+    enroll(&mut driver, Finger::new(1), model_id);
+
+    embassy_futures::block_on(async {
+        assert_eq!(driver.templete_num().await, Ok(1));
+
+        driver.uart.place_finger(Finger::new(1));
+        driver.gen_img().await.unwrap();
+        driver.gen_char(BufferID::CharBuffer1).await.unwrap();
+        let start = ModelId::new(0, 200).unwrap();
+        let result = driver
+            .search(BufferID::CharBuffer1, start, 200)
+            .await
+            .unwrap();
+        assert_eq!(result.page_id, model_id);
+
+        driver.uart.place_finger(Finger::new(2));
+        driver.gen_img().await.unwrap();
+        driver.gen_char(BufferID::CharBuffer1).await.unwrap();
+        let result = driver.search(BufferID::CharBuffer1, start, 200).await;
+        assert_eq!(
+            result,
+            Err(Error::Confirmation(
+                ConfirmationCode::FailToFindMatchingFinger
+            ))
+        );
+    });
+}
+
+#[test]
+fn simulator_no_finger() {
+    let mut driver = Driver::new(Simulator::new());
+
+    let result = embassy_futures::block_on(driver.gen_img());
+
+    assert_eq!(
+        result,
+        Err(Error::Confirmation(ConfirmationCode::NoFingerOnSensor))
+    );
+}
+
+#[test]
+fn simulator_char_roundtrip() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(3, 200).unwrap();
+    enroll(&mut driver, Finger::new(5), model_id);
+
+    embassy_futures::block_on(async {
+        driver
+            .load_char(BufferID::CharBuffer2, model_id)
+            .await
+            .unwrap();
+        let mut template = [0u8; 1536];
+        let received = driver
+            .up_char(BufferID::CharBuffer2, &mut template)
+            .await
+            .unwrap();
+        assert_eq!(received, 1536);
+        assert_eq!(Some(&template), driver.uart.template(3));
+
+        driver.empty().await.unwrap();
+        assert_eq!(driver.templete_num().await, Ok(0));
+
+        driver
+            .down_char(BufferID::CharBuffer1, &template)
+            .await
+            .unwrap();
+        driver.store(BufferID::CharBuffer1, model_id).await.unwrap();
+        assert_eq!(Some(&template), driver.uart.template(3));
+    });
+}
+
+#[test]
+fn simulator_notepad() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        driver.write_notepad(15, [0xA5; 32]).await.unwrap();
+        assert_eq!(driver.read_notepad(15).await, Ok([0xA5; 32]));
+        assert_eq!(
+            driver.read_notepad(16).await,
+            Err(Error::Confirmation(
+                ConfirmationCode::WrongNotepadPageNumber
+            ))
+        );
+    });
+}
+
+#[test]
+fn simulator_password() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        driver.set_pwd(Some(0x1234_5678)).await.unwrap();
+        driver.uart.power_cycle();
+
+        assert_eq!(
+            driver.templete_num().await,
+            Err(Error::Confirmation(ConfirmationCode::MustVerifyPassword))
+        );
+        assert_eq!(
+            driver.vfy_pwd(None).await,
+            Err(Error::Confirmation(ConfirmationCode::WrongPassword))
+        );
+        driver.vfy_pwd(Some(0x1234_5678)).await.unwrap();
+        assert_eq!(driver.templete_num().await, Ok(0));
+    });
+}
+
+#[test]
+fn simulator_system_parameters() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        let parameters = driver.read_sys_para().await.unwrap();
+        assert_eq!(parameters.finger_libary_size, 200);
+        assert_eq!(parameters.device_address, [0xFF; 4]);
+        assert_eq!(driver.packet_length(), PacketLength::Bytes128);
+
+        driver
+            .set_sys_para(ParameterSetting::DataPackageLength, 3)
+            .await
+            .unwrap();
+        assert_eq!(
+            driver
+                .set_sys_para(ParameterSetting::DataPackageLength, 4)
+                .await,
+            Err(Error::Confirmation(
+                ConfirmationCode::IncorrectConfigurationOfRegister
+            ))
+        );
+        driver.read_sys_para().await.unwrap();
+        assert_eq!(driver.packet_length(), PacketLength::Bytes128);
+
+        driver.soft_rst().await.unwrap();
+        assert_eq!(driver.packet_length(), PacketLength::Bytes256);
+    });
+}
+
+#[test]
+fn enroll_retries_poor_samples() {
+    let finger = Finger::new(9);
+    let smudge = Finger {
+        coverage: 10,
+        ..finger
+    };
+    let mut simulator = Simulator::new();
+    simulator.queue_touches([
+        Some(finger),
+        None,
+        Some(smudge),
+        None,
+        Some(finger),
+        None,
+        Some(finger),
+    ]);
+    let mut driver = Driver::new(simulator);
+
+    let model_id = ModelId::new(4, 200).unwrap();
+    let mut events = std::vec::Vec::new();
+    embassy_futures::block_on(driver.enroll(model_id, 3, 1000, |event| events.push(event)))
+        .unwrap();
+
+    use EnrollProgress::*;
+    assert_eq!(
+        events,
+        [
+            PlaceFinger(1),
+            Captured(1),
+            LiftFinger(1),
+            PlaceFinger(2),
+            Retry(
+                2,
+                ConfirmationCode::FailToGenerateCharacterLacknessOfCharacterPointOrOverSmallness
+            ),
+            LiftFinger(2),
+            PlaceFinger(2),
+            Captured(2),
+            LiftFinger(2),
+            PlaceFinger(3),
+            Captured(3),
+            Merging,
+            Stored(model_id),
+        ]
+    );
+    assert!(driver.uart.template(4).is_some());
+    assert_eq!(driver.uart.template_count(), 1);
+    assert_eq!(
+        driver.uart.aura_led(),
+        Some((LightPattern::AlwaysOn, Color::Green))
+    );
+}
+
+#[test]
+fn enroll_fails_on_different_fingers() {
+    let mut simulator = Simulator::new();
+    simulator.queue_touches([Some(Finger::new(1)), None, Some(Finger::new(2))]);
+    let mut driver = Driver::new(simulator);
+
+    let model_id = ModelId::new(0, 200).unwrap();
+    let result = embassy_futures::block_on(driver.enroll(model_id, 2, 1000, |_| {}));
+
+    assert_eq!(
+        result,
+        Err(Error::Confirmation(
+            ConfirmationCode::FailToCombineCharacterFiles
+        ))
+    );
+    assert_eq!(driver.uart.template_count(), 0);
+    assert_eq!(
+        driver.uart.aura_led(),
+        Some((LightPattern::AlwaysOn, Color::Red))
+    );
+}
+
+#[test]
+fn enroll_sample_count() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(0, 200).unwrap();
+
+    for samples in [0, 1, 7] {
+        let result = embassy_futures::block_on(driver.enroll(model_id, samples, 1000, |_| {}));
+        assert_eq!(result, Err(Error::InvalidArgument));
+    }
+}
+
+/// Adds up the time the driver waits, without waiting.
+struct Clock(std::rc::Rc<core::cell::Cell<u64>>);
+
+impl embedded_hal_async::delay::DelayNs for Clock {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.0.set(self.0.get() + ns as u64);
+    }
+}
+
+#[test]
+fn enroll_gives_up_without_finger() {
+    let elapsed = std::rc::Rc::new(core::cell::Cell::new(0));
+    let mut driver = Driver::builder(Simulator::new())
+        .delay(Clock(elapsed.clone()))
+        .build();
+    let model_id = ModelId::new(0, 200).unwrap();
+
+    let result = embassy_futures::block_on(driver.enroll(model_id, 2, 1000, |_| {}));
+
+    assert_eq!(result, Err(Error::Timeout));
+    let elapsed = elapsed.get() / 1_000_000;
+    assert!((1000..1100).contains(&elapsed), "waited {elapsed} ms");
+}
+
+#[test]
+fn identify_finger() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(12, 200).unwrap();
+    enroll(&mut driver, Finger::new(3), model_id);
+
+    embassy_futures::block_on(async {
+        driver.uart.place_finger(Finger::new(3));
+        let result = driver.identify(0, 1000).await.unwrap().unwrap();
+        assert_eq!(result.page_id, model_id);
+        assert_eq!(driver.identify(result.score + 1, 1000).await, Ok(None));
+
+        driver.uart.place_finger(Finger::new(4));
+        assert_eq!(driver.identify(0, 1000).await, Ok(None));
+    });
+}
+
+#[test]
+fn verify_finger() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(0, 200).unwrap();
+    enroll(&mut driver, Finger::new(3), model_id);
+
+    embassy_futures::block_on(async {
+        driver.uart.place_finger(Finger::new(3));
+        let score = driver.verify(model_id, 0, 1000).await.unwrap().unwrap();
+        assert_eq!(driver.verify(model_id, score + 1, 1000).await, Ok(None));
+
+        driver.uart.place_finger(Finger::new(4));
+        assert_eq!(driver.verify(model_id, 0, 1000).await, Ok(None));
+
+        let empty = ModelId::new(1, 200).unwrap();
+        assert_eq!(
+            driver.verify(empty, 0, 1000).await,
+            Err(Error::Confirmation(
+                ConfirmationCode::ErrorWhenReadingTemplateFromLibararORTemplateIsInvalid
+            ))
+        );
+    });
+}
+
+#[test]
+fn auto_enroll_steps() {
+    let mut driver = Driver::new(Simulator::new());
+    driver.uart.place_finger(Finger::new(8));
+
+    let mut steps = std::vec::Vec::new();
+    embassy_futures::block_on(async {
+        let mut enroll = driver
+            .auto_enroll_steps(5, false, false, true)
+            .await
+            .unwrap();
+        while let Some(status) = enroll.next().await {
+            let status = status.unwrap();
+            assert_eq!(status.model_id, 5);
+            steps.push(status.step);
+        }
+    });
+
+    assert_eq!(steps.len(), 15);
+    assert_eq!(steps[4], Paramater1::CollectThird);
+    assert_eq!(steps[4].sample(), Some(3));
+    assert!(steps[4].is_collect());
+    assert_eq!(steps[11].sample(), Some(6));
+    assert!(!steps[11].is_collect());
+    assert_eq!(steps.last(), Some(&Paramater1::StoreTemplates));
+    assert!(driver.uart.template(5).is_some());
+}
+
+#[test]
+fn auto_enroll_steps_end_on_failure() {
+    let mut driver = Driver::new(Simulator::new());
+    driver.uart.place_finger(Finger::new(8));
+    embassy_futures::block_on(driver.auto_enroll(0, false, false, true, true)).unwrap();
+
+    embassy_futures::block_on(async {
+        let mut enroll = driver
+            .auto_enroll_steps(1, false, false, true)
+            .await
+            .unwrap();
+        let mut last = None;
+        while let Some(status) = enroll.next().await {
+            last = Some(status);
+        }
+        assert_eq!(
+            last,
+            Some(Err(Error::Confirmation(
+                ConfirmationCode::FingerAlreadyExists
+            )))
+        );
+
+        // Nothing is left over from the steps.
+        assert_eq!(driver.templete_num().await, Ok(1));
+    });
+}
+
+#[test]
+fn auto_identify_failure_finishes_command() {
+    // AutoIdentify fails on an empty library, then TempleteNum is answered.
+    let mut rx = frame(Identifier::Acknowledge, &[0x24]);
+    rx.extend(frame(Identifier::Acknowledge, &[0x00, 0x00, 0x00]));
+    let mut driver = Driver::new(MockUart::new(&rx));
+
+    embassy_futures::block_on(async {
+        let result = driver.auto_identify(SafeGrade::Mid, 0, 200, 1, true).await;
+        assert!(matches!(result, Err(Error::Confirmation(_))));
+
+        driver.uart.tx.clear();
+        assert_eq!(driver.templete_num().await, Ok(0));
+    });
+
+    // No Cancel in front of TempleteNum.
+    assert_eq!(
+        driver.uart.tx,
+        [0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x03, 0x1D, 0x00, 0x21]
+    );
+}
+
+#[test]
+fn auto_identify_steps() {
+    let mut driver = Driver::new(Simulator::new());
+    driver.uart.place_finger(Finger::new(8));
+
+    embassy_futures::block_on(async {
+        driver
+            .auto_enroll(3, false, false, false, true)
+            .await
+            .unwrap();
+
+        let mut identify = driver
+            .auto_identify_steps(SafeGrade::Mid, 0, 200, 1)
+            .await
+            .unwrap();
+        let mut steps = std::vec::Vec::new();
+        while let Some(status) = identify.next().await {
+            steps.push(status.unwrap());
+        }
+
+        assert_eq!(
+            steps
+                .iter()
+                .map(|status| status.step)
+                .collect::<std::vec::Vec<_>>(),
+            [
+                IdentifyStep::Collect,
+                IdentifyStep::Feature,
+                IdentifyStep::Search
+            ]
+        );
+        assert_eq!(steps[2].result.page_id, ModelId::new(3, 200).unwrap());
+    });
+}
+
+/// A module that never answers.
+struct SilentUart {
+    tx: std::vec::Vec<u8>,
+}
+
+impl embedded_io_async::ErrorType for SilentUart {
+    type Error = Infallible;
+}
+
+impl embedded_io_async::Read for SilentUart {
+    async fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Self::Error> {
+        core::future::pending().await
+    }
+}
+
+impl embedded_io_async::Write for SilentUart {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+}
+
+/// Every delay is over as soon as it starts.
+struct NoWait;
+
+impl embedded_hal_async::delay::DelayNs for NoWait {
+    async fn delay_ns(&mut self, _ns: u32) {}
+}
+
+#[test]
+fn timeout_sends_cancel() {
+    let uart = SilentUart {
+        tx: std::vec::Vec::new(),
+    };
+    let mut driver = Driver::new(uart).with_delay(NoWait);
+    driver.set_timeout(Some(1000));
+
+    let result = embassy_futures::block_on(driver.gen_img());
+
+    assert_eq!(result, Err(Error::Timeout));
+    assert_eq!(
+        driver.uart.tx,
+        [
+            0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x03, 0x01, 0x00, 0x05, // GenImg
+            0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x03, 0x30, 0x00, 0x34, // Cancel
+            0xEF, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x03, 0x0F, 0x00,
+            0x13, // ReadSysPara
+        ]
+    );
+}
+
+#[test]
+fn with_timeout_restores_timeout() {
+    let uart = SilentUart {
+        tx: std::vec::Vec::new(),
+    };
+    let mut driver = Driver::new(uart).with_delay(NoWait);
+
+    let result = embassy_futures::block_on(
+        driver.with_timeout(500, async |driver| driver.handshake().await),
+    );
+
+    assert_eq!(result, Err(Error::Timeout));
+    assert_eq!(driver.timeout(), None);
+}
+
+#[test]
+fn dropped_operation_is_recovered() {
+    let mut driver = Driver::new(Simulator::new()).with_delay(NoWait);
+    driver.uart.place_finger(Finger::new(8));
+
+    embassy_futures::block_on(async {
+        {
+            let mut enroll = driver
+                .auto_enroll_steps(0, false, false, true)
+                .await
+                .unwrap();
+            enroll.next().await.unwrap().unwrap();
+        }
+
+        // The remaining steps and the answer to Cancel are thrown away.
+        assert_eq!(driver.templete_num().await, Ok(1));
+        assert_eq!(driver.templete_num().await, Ok(1));
+    });
+}
+
+#[test]
+fn dropped_operation_is_recovered_without_delay() {
+    let mut driver = Driver::new(Simulator::new());
+    driver.uart.place_finger(Finger::new(8));
+
+    embassy_futures::block_on(async {
+        {
+            let mut enroll = driver
+                .auto_enroll_steps(0, false, false, true)
+                .await
+                .unwrap();
+            enroll.next().await.unwrap().unwrap();
+        }
+
+        assert_eq!(driver.templete_num().await, Ok(1));
+        assert_eq!(driver.templete_num().await, Ok(1));
+    });
+}
+
+/// A module whose port can be told to make the reader wait once, so an operation can be dropped in the middle.
+struct Stalling {
+    module: Simulator,
+    stall: bool,
+}
+
+impl embedded_io_async::ErrorType for Stalling {
+    type Error = Infallible;
+}
+
+impl embedded_io_async::Read for Stalling {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if core::mem::take(&mut self.stall) {
+            embassy_futures::yield_now().await;
+        }
+        self.module.read(buf).await
+    }
+}
+
+impl embedded_io_async::Write for Stalling {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.module.write(buf).await
+    }
+}
+
+#[test]
+fn dropped_command_is_recovered_in_step() {
+    let mut simulator = Simulator::new();
+    let mut ready = [0u8];
+    embassy_futures::block_on(embedded_io_async::Read::read(&mut simulator, &mut ready)).unwrap();
+    let mut driver = Driver::new(Stalling {
+        module: simulator,
+        stall: false,
+    });
+
+    embassy_futures::block_on(async {
+        // Dropped after the command went out, with its answer left unread.
+        driver.uart.stall = true;
+        let dropped = select(driver.gen_img(), core::future::ready(())).await;
+        assert!(matches!(dropped, Either::Second(())));
+
+        assert_eq!(driver.templete_num().await, Ok(0));
+        assert_eq!(
+            driver.gen_img().await,
+            Err(Error::Confirmation(ConfirmationCode::NoFingerOnSensor))
+        );
+
+        // The answer to a dropped ReadSysPara looks just like the one `recover` waits for.
+        driver.uart.stall = true;
+        let dropped = select(driver.read_sys_para(), core::future::ready(())).await;
+        assert!(matches!(dropped, Either::Second(())));
+
+        assert_eq!(driver.templete_num().await, Ok(0));
+        assert_eq!(
+            driver.read_sys_para().await.map(|p| p.finger_libary_size),
+            Ok(200)
+        );
+        assert_eq!(driver.check_sensor().await, Ok(()));
+    });
+
+    assert!(driver.uart.module.output_is_empty());
+}
+
+#[test]
+fn late_sentinel_answer_is_not_taken_for_the_next() {
+    let mut simulator = Simulator::new();
+    let mut ready = [0u8];
+    embassy_futures::block_on(embedded_io_async::Read::read(&mut simulator, &mut ready)).unwrap();
+    let mut driver = Driver::builder(Stalling {
+        module: simulator,
+        stall: false,
+    })
+    .delay(NoWait)
+    .build();
+
+    embassy_futures::block_on(async {
+        driver.uart.stall = true;
+        let dropped = select(driver.gen_img(), core::future::ready(())).await;
+        assert!(matches!(dropped, Either::Second(())));
+
+        // The answers to GenImg, Cancel and the sentinel all come after `recover` gave up.
+        driver.uart.stall = true;
+        assert_eq!(driver.templete_num().await, Err(Error::Timeout));
+
+        assert_eq!(driver.templete_num().await, Ok(0));
+        assert_eq!(driver.check_sensor().await, Ok(()));
+    });
+
+    assert!(driver.uart.module.output_is_empty());
+}
+
+#[test]
+fn wait_ready_consumes_ready_byte() {
+    let mut driver = Driver::new(Simulator::new()).with_delay(NoWait);
+
+    embassy_futures::block_on(async {
+        driver.wait_ready(100).await.unwrap();
+        assert_eq!(driver.templete_num().await, Ok(0));
+    });
+
+    // Nothing the module sent is left unread.
+    assert!(driver.uart.output_is_empty());
+}
+
+#[test]
+fn wait_ready_falls_back_to_handshake() {
+    let mut simulator = Simulator::new();
+    let mut ready = [0u8];
+    embassy_futures::block_on(embedded_io_async::Read::read(&mut simulator, &mut ready)).unwrap();
+    let mut driver = Driver::new(simulator).with_delay(NoWait);
+
+    let parameters = embassy_futures::block_on(driver.init(100)).unwrap();
+
+    assert_eq!(parameters.finger_libary_size, 200);
+    assert_eq!(driver.timeout(), None);
+}
+
+#[test]
+fn wait_ready_times_out() {
+    let uart = SilentUart {
+        tx: std::vec::Vec::new(),
+    };
+    let mut driver = Driver::new(uart).with_delay(NoWait);
+
+    let result = embassy_futures::block_on(driver.wait_ready(100));
+
+    assert_eq!(result, Err(Error::Timeout));
+}
+
+fn protected_simulator(password: Password) -> Simulator {
+    let mut driver = Driver::new(Simulator::new());
+    embassy_futures::block_on(driver.set_pwd(Some(password))).unwrap();
+    driver.uart.power_cycle();
+    driver.uart
+}
+
+#[test]
+fn init_verifies_password() {
+    let mut driver = Driver::builder(protected_simulator(0xC0FFEE))
+        .password(0xC0FFEE)
+        .delay(NoWait)
+        .build();
+
+    embassy_futures::block_on(async {
+        driver.init(100).await.unwrap();
+        assert_eq!(driver.templete_num().await, Ok(0));
+    });
+}
+
+#[test]
+fn init_with_wrong_password() {
+    let mut driver = Driver::builder(protected_simulator(0xC0FFEE))
+        .password(0xBAD)
+        .build();
+
+    let result = embassy_futures::block_on(driver.init(100));
+
+    assert_eq!(
+        result,
+        Err(Error::Confirmation(ConfirmationCode::WrongPassword))
+    );
+}
+
+#[test]
+fn builder_settings() {
+    let driver = Driver::builder(Simulator::new())
+        .packet_length(PacketLength::Bytes32)
+        .baud_rate(BaudRate::Rate115200)
+        .timeout(500)
+        .build();
+
+    assert_eq!(driver.packet_length(), PacketLength::Bytes32);
+    assert_eq!(driver.baud_rate(), BaudRate::Rate115200);
+    assert_eq!(driver.password(), 0);
+    assert_eq!(driver.timeout(), Some(500));
+}
+
+#[test]
+fn set_adder_switches_address() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        driver.set_adder(0x1234_5678).await.unwrap();
+        assert_eq!(driver.address(), 0x1234_5678);
+        assert_eq!(driver.uart.address(), 0x1234_5678);
+
+        // The module only answers packages sent to its new address.
+        assert_eq!(driver.templete_num().await, Ok(0));
+        let parameters = driver.read_sys_para().await.unwrap();
+        assert_eq!(parameters.device_address, [0x12, 0x34, 0x56, 0x78]);
+    });
+}
+
+#[test]
+fn set_adder_failure_keeps_address() {
+    let mut driver = Driver::new(protected_simulator(0xC0FFEE));
+
+    let result = embassy_futures::block_on(driver.set_adder(0x1234_5678));
+
+    assert_eq!(
+        result,
+        Err(Error::Confirmation(ConfirmationCode::MustVerifyPassword))
+    );
+    assert_eq!(driver.address(), ADDRESS);
+    assert_eq!(driver.uart.address(), ADDRESS);
+}
+
+#[test]
+fn builder_address() {
+    let mut driver = Driver::new(Simulator::new());
+    embassy_futures::block_on(driver.set_adder(0xCAFE)).unwrap();
+    driver.uart.power_cycle();
+
+    let mut driver = Driver::builder(driver.uart)
+        .address(0xCAFE)
+        .delay(NoWait)
+        .build();
+
+    embassy_futures::block_on(async {
+        driver.init(100).await.unwrap();
+        assert_eq!(driver.templete_num().await, Ok(0));
+    });
+}
+
+#[test]
+fn acknowledge_from_other_address() {
+    let acknowledge = Frame {
+        address: 0xCAFE,
+        identifier: Identifier::Acknowledge,
+        contents: heapless::Vec::from_slice(&[0x00]).unwrap(),
+    };
+    let mut driver = Driver::builder(MockUart::new(&acknowledge.as_bytes().unwrap()))
+        .address(0xBEEF)
+        .build();
+
+    let result = embassy_futures::block_on(driver.handshake());
+
+    assert_eq!(result, Err(Error::Frame(FrameError::Address(0xCAFE))));
+}
+
+/// Two modules wired to the same TX and RX lines.
+struct TwoModules(Simulator, Simulator);
+
+impl embedded_io_async::ErrorType for TwoModules {
+    type Error = Infallible;
+}
+
+impl embedded_io_async::Read for TwoModules {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self.0.output_is_empty() {
+            true => self.1.read(buf).await,
+            false => self.0.read(buf).await,
+        }
+    }
+}
+
+impl embedded_io_async::Write for TwoModules {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0.write_all(buf).await?;
+        self.1.write_all(buf).await?;
+        Ok(buf.len())
+    }
+}
+
+const ENTRY: Address = 0x0000_0001;
+const EXIT: Address = 0x0000_0002;
+
+fn addressed_simulator(address: Address) -> Simulator {
+    let mut driver = Driver::new(Simulator::new());
+    embassy_futures::block_on(driver.set_adder(address)).unwrap();
+    driver.uart.power_cycle();
+    driver.uart
+}
+
+#[test]
+fn sensor_bus_addresses_modules() {
+    let mut entry = addressed_simulator(ENTRY);
+    entry.place_finger(Finger::new(1));
+    let bus = SensorBus::<NoopRawMutex, _>::new(TwoModules(entry, addressed_simulator(EXIT)));
+
+    embassy_futures::block_on(async {
+        let mut entry = bus.sensor(ENTRY).build();
+        let mut exit = bus.sensor(EXIT).build();
+
+        entry
+            .exclusive(async |driver| driver.init(100).await.map(|_| ()))
+            .await
+            .unwrap();
+        exit.exclusive(async |driver| driver.init(100).await.map(|_| ()))
+            .await
+            .unwrap();
+
+        entry
+            .exclusive(async |driver| driver.gen_img().await)
+            .await
+            .unwrap();
+        let result = exit.exclusive(async |driver| driver.gen_img().await).await;
+        assert_eq!(
+            result,
+            Err(Error::Confirmation(ConfirmationCode::NoFingerOnSensor))
+        );
+    });
+
+    let TwoModules(entry, exit) = bus.into_inner();
+    assert!(entry.output_is_empty());
+    assert!(exit.output_is_empty());
+}
+
+#[test]
+fn sensor_bus_takes_turns() {
+    let bus = SensorBus::<NoopRawMutex, _>::new(TwoModules(
+        addressed_simulator(ENTRY),
+        addressed_simulator(EXIT),
+    ));
+    let mut entry = bus.sensor(ENTRY).build();
+    let mut exit = bus.sensor(EXIT).build();
+
+    let (entry, exit) = embassy_futures::block_on(embassy_futures::join::join(
+        entry.exclusive(async |driver| {
+            driver.write_notepad(0, [0x0E; 32]).await?;
+            driver.read_notepad(0).await
+        }),
+        exit.exclusive(async |driver| {
+            driver.write_notepad(0, [0xE0; 32]).await?;
+            driver.read_notepad(0).await
+        }),
+    ));
+
+    assert_eq!(entry, Ok([0x0E; 32]));
+    assert_eq!(exit, Ok([0xE0; 32]));
+}
+
+#[test]
+fn sensor_bus_released_after_failure() {
+    let bus = SensorBus::<NoopRawMutex, _>::new(TwoModules(
+        addressed_simulator(ENTRY),
+        addressed_simulator(EXIT),
+    ));
+
+    embassy_futures::block_on(async {
+        // No module answers at this address, so its GenImg stays unanswered.
+        let mut ghost = bus.sensor(0x0000_0003).build();
+        let mut exit = bus.sensor(EXIT).delay(NoWait).build();
+
+        let result = ghost.exclusive(async |driver| driver.gen_img().await).await;
+        assert_eq!(result, Err(Error::UnexpectedEof));
+
+        let stuck = async {
+            for _ in 0..10 {
+                embassy_futures::yield_now().await;
+            }
+        };
+        let result = select(
+            exit.exclusive(async |driver| driver.templete_num().await),
+            stuck,
+        )
+        .await;
+        assert!(matches!(result, Either::First(Ok(0))));
+    });
+
+    let TwoModules(entry, exit) = bus.into_inner();
+    assert!(entry.output_is_empty());
+    assert!(exit.output_is_empty());
+}
+
+const PROVISIONED: Credentials = Credentials {
+    address: 0xCAFE,
+    password: 0xC0FFEE,
+};
+
+#[test]
+fn provision_changes_credentials() {
+    let mut driver = Driver::builder(Simulator::new()).delay(NoWait).build();
+
+    embassy_futures::block_on(async {
+        driver.init(100).await.unwrap();
+        driver.provision(PROVISIONED, 100).await.unwrap();
+    });
+
+    assert_eq!(driver.credentials(), PROVISIONED);
+    assert_eq!(driver.uart.address(), 0xCAFE);
+    assert_eq!(driver.uart.password(), 0xC0FFEE);
+
+    driver.uart.power_cycle();
+    let mut driver = Driver::builder(driver.uart)
+        .address(0xCAFE)
+        .password(0xC0FFEE)
+        .delay(NoWait)
+        .build();
+    embassy_futures::block_on(driver.init(100)).unwrap();
+}
+
+#[test]
+fn provision_rolls_back() {
+    let mut simulator = Simulator::new();
+    simulator.set_flash_fault(Some(Instruction::SetPwd));
+    let mut driver = Driver::builder(simulator).delay(NoWait).build();
+
+    let result = embassy_futures::block_on(async {
+        driver.init(100).await.unwrap();
+        driver.provision(PROVISIONED, 100).await
+    });
+
+    assert_eq!(
+        result,
+        Err(Error::Confirmation(ConfirmationCode::ErrorWhenWritingFlash))
+    );
+    assert_eq!(driver.credentials(), Credentials::default());
+    assert_eq!(driver.uart.address(), ADDRESS);
+    assert_eq!(driver.uart.password(), 0);
+}
+
+#[test]
+fn provision_reports_failed_rollback() {
+    let mut driver = Driver::builder(SilentUart {
+        tx: std::vec::Vec::new(),
+    })
+    .delay(NoWait)
+    .build();
+
+    let result = embassy_futures::block_on(driver.provision(PROVISIONED, 100));
+
+    assert_eq!(result, Err(Error::CredentialsUnknown));
+}
+
+#[test]
+fn probe_known_credentials() {
+    let mut driver = Driver::builder(Simulator::new()).delay(NoWait).build();
+    embassy_futures::block_on(driver.provision(PROVISIONED, 100)).unwrap();
+    driver.uart.power_cycle();
+
+    let mut driver = Driver::builder(driver.uart).delay(NoWait).build();
+    let known = [
+        Credentials::default(),
+        Credentials {
+            address: 0xCAFE,
+            password: 0xBAD,
+        },
+        PROVISIONED,
+    ];
+
+    let found = embassy_futures::block_on(driver.probe(&known, 100));
+
+    assert_eq!(found, Ok(Some(PROVISIONED)));
+    assert_eq!(driver.credentials(), PROVISIONED);
+    assert_eq!(embassy_futures::block_on(driver.templete_num()), Ok(0));
+}
+
+#[test]
+fn probe_without_match() {
+    let mut driver = Driver::builder(protected_simulator(0xC0FFEE))
+        .delay(NoWait)
+        .build();
+
+    let found = embassy_futures::block_on(driver.probe(&[Credentials::default()], 100));
+
+    assert_eq!(found, Ok(None));
+    assert_eq!(driver.credentials(), Credentials::default());
+}
+
+#[test]
+fn searches_need_a_delay() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        assert_eq!(
+            driver.probe(&[Credentials::default()], 100).await,
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            driver.provision(PROVISIONED, 100).await,
+            Err(Error::InvalidArgument)
+        );
+        assert_eq!(
+            driver.autobaud(100, |_, _| {}).await,
+            Err(Error::InvalidArgument)
+        );
+    });
+
+    assert_eq!(driver.uart.address(), ADDRESS);
+}
+
+#[test]
+fn set_packet_length_with_reset() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(0, 200).unwrap();
+    enroll(&mut driver, Finger::new(2), model_id);
+    let mut driver = driver.with_delay(NoWait);
+
+    embassy_futures::block_on(async {
+        driver.init(100).await.unwrap();
+        driver
+            .set_packet_length(PacketLength::Bytes32, true)
+            .await
+            .unwrap();
+        assert_eq!(driver.packet_length(), PacketLength::Bytes32);
+
+        let mut template = [0u8; 1536];
+        driver
+            .load_char(BufferID::CharBuffer1, model_id)
+            .await
+            .unwrap();
+        driver
+            .up_char(BufferID::CharBuffer1, &mut template)
+            .await
+            .unwrap();
+        driver
+            .down_char(BufferID::CharBuffer2, &template)
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(driver.uart.packet_length(), PacketLength::Bytes32);
+    assert!(driver.uart.output_is_empty());
+}
+
+#[test]
+fn set_packet_length_takes_effect_on_power_on() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(0, 200).unwrap();
+    enroll(&mut driver, Finger::new(2), model_id);
+
+    embassy_futures::block_on(async {
+        driver
+            .set_packet_length(PacketLength::Bytes64, false)
+            .await
+            .unwrap();
+        assert_eq!(driver.packet_length(), PacketLength::Bytes128);
+
+        let mut template = [0u8; 1536];
+        driver
+            .up_char(BufferID::CharBuffer1, &mut template)
+            .await
+            .unwrap();
+        driver
+            .down_char(BufferID::CharBuffer2, &template)
+            .await
+            .unwrap();
+    });
+
+    assert_eq!(driver.uart.packet_length(), PacketLength::Bytes64);
+}
+
+#[test]
+fn stored_packet_length_waits_for_restart() {
+    let mut driver = Driver::new(Simulator::new()).with_delay(NoWait);
+
+    embassy_futures::block_on(async {
+        driver
+            .set_packet_length(PacketLength::Bytes32, false)
+            .await
+            .unwrap();
+        driver
+            .set_security_level(SecurityLevel::Level4, false)
+            .await
+            .unwrap();
+        driver.wait_until_idle(100).await.unwrap();
+        assert_eq!(driver.packet_length(), PacketLength::Bytes128);
+        assert_eq!(driver.uart.active_packet_length(), PacketLength::Bytes128);
+
+        driver.soft_rst().await.unwrap();
+        driver.wait_ready(100).await.unwrap();
+        assert_eq!(driver.packet_length(), PacketLength::Bytes32);
+        assert_eq!(driver.uart.active_packet_length(), PacketLength::Bytes32);
+    });
+}
+
+#[test]
+fn set_security_level_only_writes_changes() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        driver
+            .set_security_level(SecurityLevel::Level5, false)
+            .await
+            .unwrap();
+        assert_eq!(driver.uart.security_level(), SecurityLevel::Level5);
+
+        driver.uart.set_flash_fault(Some(Instruction::SetSysPara));
+        driver
+            .set_security_level(SecurityLevel::Level5, true)
+            .await
+            .unwrap();
+        assert_eq!(
+            driver
+                .set_security_level(SecurityLevel::Level1, false)
+                .await,
+            Err(Error::Confirmation(ConfirmationCode::ErrorWhenWritingFlash))
+        );
+    });
+}
+
+#[test]
+fn set_baud_rate_with_reset() {
+    let mut driver = Driver::new(Simulator::new());
+
+    let mut configured = None;
+
+    embassy_futures::block_on(async {
+        driver
+            .set_baud_rate_with_reset(BaudRate::Rate115200, |_, baud_rate| {
+                configured = Some(baud_rate)
+            })
+            .await
+            .unwrap();
+        assert_eq!(driver.templete_num().await, Ok(0));
+    });
+
+    assert_eq!(configured, Some(BaudRate::Rate115200));
+    assert_eq!(driver.baud_rate(), BaudRate::Rate115200);
+    assert_eq!(driver.uart.baud_rate(), BaudRate::Rate115200);
+    assert!(driver.uart.output_is_empty());
+}
+
+/// A module on a line that only gets through at the module's baud rate.
+struct Line {
+    module: Simulator,
+    baud_rate: BaudRate,
+}
+
+impl embedded_io_async::ErrorType for Line {
+    type Error = Infallible;
+}
+
+impl embedded_io_async::Read for Line {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        match self.baud_rate == self.module.baud_rate() {
+            true => self.module.read(buf).await,
+            false => Ok(0),
+        }
+    }
+}
+
+impl embedded_io_async::Write for Line {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.baud_rate == self.module.baud_rate() {
+            self.module.write_all(buf).await?;
+        }
+        Ok(buf.len())
+    }
+}
+
+#[test]
+fn autobaud_finds_rate() {
+    let mut driver = Driver::new(Simulator::new());
+    embassy_futures::block_on(driver.set_baud_rate(BaudRate::Rate19200)).unwrap();
+    driver.uart.power_cycle();
+    let line = Line {
+        module: driver.uart,
+        baud_rate: BaudRate::Rate57600,
+    };
+    let mut driver = Driver::builder(line).delay(NoWait).build();
+
+    let found = embassy_futures::block_on(driver.autobaud(100, |line, baud_rate| {
+        line.baud_rate = baud_rate;
+    }))
+    .unwrap();
+
+    let (baud_rate, parameters) = found.unwrap();
+    assert_eq!(baud_rate, BaudRate::Rate19200);
+    assert_eq!(parameters.buad_setting, 2);
+    assert_eq!(driver.baud_rate(), BaudRate::Rate19200);
+    assert_eq!(driver.uart.baud_rate, BaudRate::Rate19200);
+}
+
+#[test]
+fn autobaud_without_answer() {
+    let mut driver = Driver::builder(SilentUart {
+        tx: std::vec::Vec::new(),
+    })
+    .delay(NoWait)
+    .build();
+    let mut tried = std::vec::Vec::new();
+
+    let found = embassy_futures::block_on(driver.autobaud(100, |_, baud_rate| {
+        tried.push(baud_rate.bps());
+    }));
+
+    assert_eq!(found, Ok(None));
+    assert_eq!(tried, [57600, 9600, 19200, 38400, 115200, 57600]);
+    assert_eq!(driver.baud_rate(), BaudRate::Rate57600);
+}
+
+#[test]
+fn system_register_flags() {
+    let register = SystemRegister::from(0b1001);
+
+    assert!(register.is_busy());
+    assert!(register.image_buffer_valid());
+    assert!(!register.pass());
+    assert!(!register.password_verified());
+    assert_eq!(
+        register,
+        SystemRegister::BUSY | SystemRegister::IMG_BUF_STAT
+    );
+}
+
+#[test]
+fn read_sys_para_status_register() {
+    let mut driver = Driver::builder(protected_simulator(0xC0FFEE))
+        .password(0xC0FFEE)
+        .build();
+    driver.uart.place_finger(Finger::new(1));
+
+    let register = embassy_futures::block_on(async {
+        driver.init(100).await.unwrap();
+        driver.gen_img().await.unwrap();
+        driver.read_sys_para().await.map(|p| p.status_register)
+    })
+    .unwrap();
+
+    assert!(register.password_verified());
+    assert!(register.image_buffer_valid());
+    assert!(!register.is_busy());
+}
+
+#[test]
+fn wait_until_idle() {
+    let mut driver = Driver::new(Simulator::new());
+
+    driver.uart.keep_busy(3);
+    let register = embassy_futures::block_on(driver.wait_until_idle(100));
+    assert_eq!(register.map(|register| register.is_busy()), Ok(false));
+
+    driver.uart.keep_busy(100);
+    let register = embassy_futures::block_on(driver.wait_until_idle(20));
+    assert_eq!(register, Err(Error::Timeout));
+}
+
+#[test]
+fn library_index() {
+    let mut driver = Driver::new(Simulator::new());
+    for id in [0, 1, 5, 130] {
+        enroll(
+            &mut driver,
+            Finger::new(id as u32),
+            ModelId::new(id, 200).unwrap(),
+        );
+    }
+
+    let index = embassy_futures::block_on(driver.read_library_index()).unwrap();
+
+    assert_eq!(index.count(), 4);
+    assert_eq!(index.first_free(), ModelId::new(2, 200));
+    assert!(index.is_occupied(ModelId::new(130, 200).unwrap()));
+    assert!(!index.is_occupied(ModelId::new(131, 200).unwrap()));
+    let occupied: std::vec::Vec<u16> = index.iter_occupied().map(ModelId::get).collect();
+    assert_eq!(occupied, [0, 1, 5, 130]);
+}
+
+#[test]
+fn library_index_pages() {
+    let mut tables = [[0u8; 32]; 4];
+    tables[0] = [0xFF; 32];
+    tables[1][0] = 0b0000_0101;
+
+    let index = LibraryIndex::new(tables, 300);
+
+    assert_eq!(index.count(), 258);
+    assert_eq!(index.first_free(), ModelId::new(257, 300));
+    assert!(index.is_occupied(ModelId::new(258, 300).unwrap()));
+    assert!(!index.is_occupied(ModelId::new(300, 1000).unwrap()));
+
+    let full = LibraryIndex::new([[0xFF; 32]; 4], 1000);
+    assert_eq!(full.first_free(), None);
+}
+
+#[test]
+fn upload_image() {
+    let mut driver = Driver::new(Simulator::new());
+    driver.uart.place_finger(Finger::new(8));
+    let mut packed = PackedImage::default();
+    let mut image = [0u8; 192 * 192];
+
+    embassy_futures::block_on(async {
+        driver.gen_img().await.unwrap();
+        driver.upload_image(&mut packed).await.unwrap();
+    });
+    packed.unpack(&mut image);
+
+    let captured = Simulator::image_for(Finger::new(8));
+    assert_eq!(packed, PackedImage::pack(&captured));
+    for (pixel, captured) in image.iter().zip(captured.iter()) {
+        assert_eq!(pixel >> 4, captured >> 4);
+    }
+}
+
+#[test]
+fn packed_image_roundtrip() {
+    let mut data = [0u8; PACKED_IMAGE_SIZE];
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    let packed = PackedImage::new(data);
+    let mut image = [0u8; 192 * 192];
+
+    packed.unpack(&mut image);
+
+    assert_eq!(&image[..4], &[0x00, 0x00, 0x00, 0x11]);
+    assert_eq!(image[2 * 0xFF + 1], 0xFF);
+    assert_eq!(PackedImage::pack(&image), packed);
+}
+
+#[test]
+fn download_image() {
+    let mut driver = Driver::new(Simulator::new());
+    driver.uart.place_finger(Finger::new(8));
+    let mut packed = PackedImage::default();
+
+    embassy_futures::block_on(async {
+        driver.gen_img().await.unwrap();
+        driver.upload_image(&mut packed).await.unwrap();
+        driver.uart.lift_finger();
+
+        driver.download_image(&packed).await.unwrap();
+        driver.gen_char(BufferID::CharBuffer1).await.unwrap();
+    });
+}
+
+#[test]
+fn download_image_round_trip() {
+    let mut driver = Driver::new(Simulator::new());
+    let mut data = [0u8; PACKED_IMAGE_SIZE];
+    for (index, byte) in data.iter_mut().enumerate() {
+        *byte = index as u8;
+    }
+    let image = PackedImage::new(data);
+    let mut packed = PackedImage::default();
+
+    embassy_futures::block_on(async {
+        driver.download_image(&image).await.unwrap();
+        driver.upload_image(&mut packed).await.unwrap();
+    });
+
+    assert_eq!(packed.as_bytes(), image.as_bytes());
+}
+
+#[test]
+fn down_image_rejects_wrong_length() {
+    let mut driver = Driver::new(MockUart::new(&[]));
+
+    let result = embassy_futures::block_on(driver.down_image(&[0xAB; 1024]));
+
+    assert_eq!(result, Err(Error::InvalidArgument));
+    assert!(driver.uart.tx.is_empty());
+}
+
+#[test]
+fn upload_image_incomplete() {
+    let mut data = frame(Identifier::Acknowledge, &[0x00]);
+    data.extend(frame(Identifier::End, &[0xAB; 64]));
+    let mut driver = Driver::new(MockUart::new(&data));
+
+    let result = embassy_futures::block_on(driver.upload_image(&mut PackedImage::default()));
+
+    assert_eq!(result, Err(Error::IncompleteData));
+}
+
+fn gradient() -> ImageData {
+    let mut image = [0u8; 192 * 192];
+    for (i, pixel) in image.iter_mut().enumerate() {
+        *pixel = (i % 192) as u8;
+    }
+    image
+}
+
+#[test]
+fn export_pgm() {
+    let mut file = std::vec::Vec::new();
+
+    r503::export::write_pgm(&gradient(), &mut file).unwrap();
+
+    let header = b"P5\n# 508 dpi\n192 192\n255\n";
+    assert_eq!(&file[..header.len()], header);
+    assert_eq!(&file[header.len()..], &gradient()[..]);
+}
+
+#[test]
+fn export_bmp() {
+    let mut image = gradient();
+    image[0] = 0xFF;
+    let mut file = std::vec::Vec::new();
+
+    r503::export::write_bmp(&image, &mut file).unwrap();
+
+    let u32_at = |offset: usize| u32::from_le_bytes(file[offset..offset + 4].try_into().unwrap());
+    assert_eq!(&file[..2], b"BM");
+    assert_eq!(u32_at(2) as usize, file.len());
+    assert_eq!(u32_at(10), 1078);
+    assert_eq!((u32_at(18), u32_at(22)), (192, 192));
+    assert_eq!((u32_at(38), u32_at(42)), (20000, 20000));
+    assert_eq!(&file[54 + 4 * 0x80..][..4], &[0x80, 0x80, 0x80, 0]);
+    // Rows are stored bottom up.
+    assert_eq!(file[1078 + 191 * 192], 0xFF);
+    assert_eq!(&file[1078..1078 + 192], &image[191 * 192..]);
+}
+
+#[test]
+fn export_png() {
+    let mut file = std::vec::Vec::new();
+
+    r503::export::write_png(&gradient(), &mut file).unwrap();
+
+    let mut reader = png::Decoder::new(file.as_slice()).read_info().unwrap();
+    let dimensions = reader.info().pixel_dims.unwrap();
+    assert_eq!(
+        (dimensions.xppu, dimensions.unit),
+        (20000, png::Unit::Meter)
+    );
+    let mut pixels = std::vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(pixels, gradient());
+}
+
+#[test]
+fn replay_recorded_image() {
+    let mut driver = Driver::new(Simulator::new());
+    let model_id = ModelId::new(7, 200).unwrap();
+    enroll(&mut driver, Finger::new(3), model_id);
+    driver.uart.place_finger(Finger::new(3));
+    let mut packed = PackedImage::default();
+    let mut recorded = [0u8; 192 * 192];
+
+    embassy_futures::block_on(async {
+        driver.gen_img().await.unwrap();
+        driver.upload_image(&mut packed).await.unwrap();
+        packed.unpack(&mut recorded);
+        driver.uart.lift_finger();
+        driver.uart.power_cycle();
+
+        driver
+            .replay_image(&recorded, BufferID::CharBuffer1)
+            .await
+            .unwrap();
+        let found = driver
+            .search(BufferID::CharBuffer1, ModelId::default(), 200)
+            .await
+            .unwrap();
+        assert_eq!(found.page_id, model_id);
+    });
+}
+
+#[test]
+fn replay_is_repeatable() {
+    let mut driver = Driver::new(Simulator::new());
+
+    embassy_futures::block_on(async {
+        driver
+            .replay_image(&gradient(), BufferID::CharBuffer1)
+            .await
+            .unwrap();
+        driver
+            .replay_image(&gradient(), BufferID::CharBuffer2)
+            .await
+            .unwrap();
+        driver.r#match().await.unwrap();
+    });
+}
+
+/// What the sensor captures for `finger`, with the finger's pixels changed by `shade`.
+fn shaded_image(finger: Finger, shade: impl Fn(u8) -> u8) -> ImageData {
+    let mut image = *Simulator::image_for(finger);
+    for pixel in image.iter_mut().filter(|pixel| **pixel != 0xFF) {
+        *pixel = shade(*pixel);
+    }
+    image
+}
+
+#[test]
+fn quality_of_good_image() {
+    let quality = Quality::assess(&Simulator::image_for(Finger::new(1)));
+
+    assert!(quality.is_acceptable(), "{quality:?}");
+    assert!(quality.coverage >= 70, "{quality:?}");
+    assert!(quality.score() >= 80, "{quality:?}");
+}
+
+#[test]
+fn quality_hints() {
+    let hints = |image: &ImageData| Quality::assess(image).hints().to_vec();
+
+    assert_eq!(hints(&[0xFF; 192 * 192]), [Hint::PlaceFinger]);
+
+    let small = Finger {
+        id: 1,
+        coverage: 15,
+    };
+    assert_eq!(hints(&Simulator::image_for(small)), [Hint::PressHarder]);
+
+    let wet = shaded_image(Finger::new(1), |pixel| 0x30 + (pixel & 0x07));
+    assert_eq!(hints(&wet), [Hint::TooWet]);
+
+    let dry = shaded_image(Finger::new(1), |pixel| 0xB0 + (pixel & 0x07));
+    assert_eq!(hints(&dry), [Hint::TooDry]);
+
+    // The finger sits low on the sensor.
+    let mut low = [0xFF; 192 * 192];
+    low[64 * 192..].copy_from_slice(&Simulator::image_for(Finger::new(1))[..128 * 192]);
+    assert!(hints(&low).contains(&Hint::MoveUp), "{:?}", hints(&low));
+}
+
+#[test]
+fn backup_and_restore_library() {
+    let mut driver = Driver::new(Simulator::new());
+    for id in [0, 4, 199] {
+        enroll(
+            &mut driver,
+            Finger::new(id as u32),
+            ModelId::new(id, 200).unwrap(),
+        );
+    }
+
+    let archive = embassy_futures::block_on(driver.backup_library()).unwrap();
+    let ids: std::vec::Vec<u16> = archive
+        .entries
+        .iter()
+        .map(|entry| entry.model_id.get())
+        .collect();
+    assert_eq!(ids, [0, 4, 199]);
+    assert_eq!(Some(&*archive.entries[1].template), driver.uart.template(4));
+    assert_eq!(archive.security_level, 3);
+    assert_eq!(archive.firmware, "SIM-1.0.00");
+
+    let mut archive = archive;
+    archive.entries[1].label = "Ada, left thumb".into();
+    let mut file = std::vec::Vec::new();
+    archive.write_to(&mut file).unwrap();
+    assert_eq!(
+        file.len(),
+        14 + 2 + 11 + 3 * (2 + 8 + 1 + 1536 + 4) + "Ada, left thumb".len()
+    );
+    let archive = Archive::read_from(file.as_slice()).unwrap();
+    assert_eq!(archive.entries[1].label, "Ada, left thumb");
+    assert_eq!(archive.firmware, "SIM-1.0.00");
+
+    // A factory-new replacement.
+    let mut replacement = Driver::new(Simulator::new());
+    embassy_futures::block_on(replacement.restore_library(&archive)).unwrap();
+    for id in [0, 4, 199] {
+        assert_eq!(replacement.uart.template(id), driver.uart.template(id));
+    }
+    assert_eq!(replacement.uart.template_count(), 3);
+
+    replacement.uart.place_finger(Finger::new(4));
+    let found = embassy_futures::block_on(replacement.identify(0, 1000)).unwrap();
+    assert_eq!(found.map(|found| found.page_id.get()), Some(4));
+}
+
+#[test]
+fn restore_library_too_small() {
+    let archive = Archive {
+        library_size: 1000,
+        security_level: 3,
+        firmware: "SIM-1.0.00".into(),
+        entries: std::vec![Entry {
+            model_id: ModelId::new(500, 1000).unwrap(),
+            backed_up: 1_700_000_000,
+            label: std::string::String::new(),
+            template: std::boxed::Box::new(Simulator::template_for(Finger::new(5))),
+        }],
+    };
+    let mut driver = Driver::new(Simulator::new());
+
+    let result = embassy_futures::block_on(driver.restore_library(&archive));
+
+    assert_eq!(result, Err(Error::InvalidArgument));
+    assert_eq!(driver.uart.template_count(), 0);
+}
+
+#[test]
+fn archive_checksum() {
+    let archive = Archive {
+        library_size: 200,
+        security_level: 5,
+        firmware: "SIM-1.0.00".into(),
+        entries: std::vec![Entry {
+            model_id: ModelId::new(9, 200).unwrap(),
+            backed_up: 1_700_000_000,
+            label: "door".into(),
+            template: std::boxed::Box::new(Simulator::template_for(Finger::new(9))),
+        }],
+    };
+    let mut file = std::vec::Vec::new();
+    archive.write_to(&mut file).unwrap();
+    assert_eq!(Archive::read_from(file.as_slice()).unwrap(), archive);
+
+    file[14 + 13 + 15 + 100] ^= 0x01;
+    let result = Archive::read_from(file.as_slice());
+    assert!(matches!(result, Err(ArchiveError::Checksum(model_id)) if model_id.get() == 9));
+
+    file[0] = b'X';
+    assert!(matches!(
+        Archive::read_from(file.as_slice()),
+        Err(ArchiveError::Magic)
+    ));
+}